@@ -6,33 +6,62 @@ use nom::{
 	Finish,
 };
 
+use ivory_runtime::runtime::Runtime;
+use ivory_tokenizer::{tokenize, variable::VariableName};
+
 use crate::error::ReplError;
 
-pub fn commands(
-) -> &'static [(&'static str, fn(&str) -> Result<String, ReplError>)] {
-	&[("set", set), ("load", load), ("unload", unload)]
+type Command = fn(&mut Runtime, &str) -> Result<String, ReplError>;
+
+pub fn commands() -> &'static [(&'static str, Command)] {
+	&[
+		("set", set),
+		("load", load),
+		("unload", unload),
+		("deps", deps),
+		("rdeps", rdeps),
+	]
 }
 
-pub fn set(arg: &str) -> Result<String, ReplError> {
-	todo!();
+/// Changes a variable's definition in the file it's defined in
+pub fn set(runtime: &mut Runtime, arg: &str) -> Result<String, ReplError> {
+	let (name, value) = arg.split_once('=').ok_or_else(|| {
+		ReplError::CommandParsingError("expected `name = value`".to_string())
+	})?;
+	let name = tokenize::<VariableName>(name.trim())?;
+	runtime.edit_variable(&name.0, value)?;
+	Ok(format!("{} = {}", name, runtime.run_val(&name.0)?))
 }
 
-pub fn load(arg: &str) -> Result<String, ReplError> {
+pub fn load(_: &mut Runtime, arg: &str) -> Result<String, ReplError> {
 	todo!();
 }
 
-pub fn unload(arg: &str) -> Result<String, ReplError> {
+pub fn unload(_: &mut Runtime, arg: &str) -> Result<String, ReplError> {
 	todo!();
 }
 
-struct CommandCall {
+/// Shows everything a variable depends on
+pub fn deps(runtime: &mut Runtime, arg: &str) -> Result<String, ReplError> {
+	Ok(runtime.dependencies(arg.trim())?.to_string())
+}
+
+/// Shows everything that depends on a variable
+pub fn rdeps(runtime: &mut Runtime, arg: &str) -> Result<String, ReplError> {
+	Ok(runtime.dependents(arg.trim())?.to_string())
+}
+
+pub struct CommandCall {
 	name: String,
 	arg: String,
 }
 
 impl CommandCall {
-	pub fn parse_run(input: &str) -> Result<String, ReplError> {
-		CommandCall::parse(input)?.run()
+	pub fn parse_run(
+		runtime: &mut Runtime,
+		input: &str,
+	) -> Result<String, ReplError> {
+		CommandCall::parse(input)?.run(runtime)
 	}
 
 	pub fn parse(input: &str) -> Result<Self, ReplError> {
@@ -49,10 +78,10 @@ impl CommandCall {
 		})
 		.map(|(_, cmd)| cmd)
 	}
-	pub fn run(&self) -> Result<String, ReplError> {
+	pub fn run(&self, runtime: &mut Runtime) -> Result<String, ReplError> {
 		for (name, f) in commands() {
 			if name == &self.name {
-				return f(&self.arg);
+				return f(runtime, &self.arg);
 			}
 		}
 		Err(ReplError::CommandNotFound(self.name.clone()))
@@ -23,7 +23,7 @@ impl ModLoader for FileLoader {
 		url: &str,
 		parent_path: &str,
 	) -> Result<String, ModLoaderError> {
-		if url.starts_with("http://") || url.starts_with("https://") {
+		if is_remote(url) {
 			match self.loaded_files.get(url) {
 				Some(LocalSource::Web { cache }) => Ok(cache.clone()),
 				None => {
@@ -37,14 +37,7 @@ impl ModLoader for FileLoader {
 				Some(LocalSource::File { .. }) => unreachable!(),
 			}
 		} else {
-			let rel_p = RelativePath::new(url);
-			let mut p = PathBuf::from_str(parent_path).map_err(|e| {
-				ModLoaderError::ErrorLoadingModule(url.to_string(), e.to_string())
-			})?;
-			if p.is_file() {
-				p.pop();
-			}
-			let final_path = rel_p.to_logical_path(p);
+			let final_path = local_path(url, parent_path)?;
 
 			self.current_file = Some(final_path.clone());
 
@@ -57,6 +50,27 @@ impl ModLoader for FileLoader {
 		}
 	}
 
+	fn rewrite(
+		&mut self,
+		url: &str,
+		parent_path: &str,
+		new_value: &str,
+	) -> Result<(), ModLoaderError> {
+		if is_remote(url) {
+			return Err(ModLoaderError::CannotRewrite(
+				url.to_string(),
+				"modules loaded from the web can't be changed".to_string(),
+			));
+		}
+		let final_path = local_path(url, parent_path)?;
+		std::fs::write(&final_path, new_value).map_err(|e| {
+			ModLoaderError::CannotRewrite(
+				final_path.as_os_str().to_str().unwrap().to_string(),
+				e.to_string(),
+			)
+		})
+	}
+
 	fn zinger(&self) -> Option<String> {
 		if let Some(path) = &self.current_file {
 			path.file_name().map(|os| os.to_string_lossy().to_string())
@@ -66,6 +80,22 @@ impl ModLoader for FileLoader {
 	}
 }
 
+fn is_remote(url: &str) -> bool {
+	url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// Resolves the path of a local module relative to the module that uses it
+fn local_path(url: &str, parent_path: &str) -> Result<PathBuf, ModLoaderError> {
+	let rel_p = RelativePath::new(url);
+	let mut p = PathBuf::from_str(parent_path).map_err(|e| {
+		ModLoaderError::ErrorLoadingModule(url.to_string(), e.to_string())
+	})?;
+	if p.is_file() {
+		p.pop();
+	}
+	Ok(rel_p.to_logical_path(p))
+}
+
 pub enum LocalSource {
 	File { path: String },
 	Web { cache: String },
@@ -2,9 +2,10 @@ use colored::*;
 use ivory_runtime::runtime::Runtime;
 use rustyline::hint::{Hint, Hinter};
 use rustyline_derive::{Completer, Helper, Highlighter, Validator};
+use std::cell::RefCell;
 
 #[derive(Helper, Completer, Highlighter, Validator)]
-pub struct RuntimeHinter<'a>(pub &'a Runtime);
+pub struct RuntimeHinter<'a>(pub &'a RefCell<Runtime>);
 
 impl<'a> Hinter for RuntimeHinter<'a> {
 	type Hint = AppHint;
@@ -28,11 +29,11 @@ impl<'a> Hinter for RuntimeHinter<'a> {
 				}
 			}
 			let var_name = &line[start_of_variable..];
-			self.0.values.variable_names().into_iter().find_map(|key| {
+			let runtime = self.0.borrow();
+			runtime.values.variable_names().into_iter().find_map(|key| {
 				if var_name.len() > 0 && key.starts_with(var_name) {
 					let skip = var_name.len()..;
-					let val = self
-						.0
+					let val = runtime
 						.values
 						.get_variable(&key)
 						.map(|v| v.to_string())
@@ -1,6 +1,6 @@
 use colored::*;
 use hint::RuntimeHinter;
-use std::path::Path;
+use std::{cell::RefCell, path::Path};
 
 mod commands;
 mod error;
@@ -8,29 +8,44 @@ mod files;
 mod format;
 mod hint;
 
-use crate::error::ReplError;
-use clap::Arg;
+use crate::{commands::CommandCall, error::ReplError};
+use clap::{Arg, SubCommand};
 use files::FileLoader;
 use ivory_runtime::{
 	runtime::{Runtime, RuntimeContext},
 	value::Value,
 };
+use ivory_tokenizer::{tokenize, variable::Variable};
 use rustyline::{error::ReadlineError, Editor};
 struct App<'a> {
-	runtime: &'a Runtime,
+	runtime: &'a RefCell<Runtime>,
 }
 
 impl<'a> App<'a> {
 	fn run(&mut self, cmd: &str) -> Result<(), ReplError> {
-		let res_eq = self.runtime.run(cmd)?.un_nest();
+		if let Ok(call) = CommandCall::parse(cmd) {
+			println!("{}", call.run(&mut self.runtime.borrow_mut())?);
+			return Ok(());
+		}
+		// `name = value` changes the variable in its source file
+		if tokenize::<Variable>(cmd.trim().trim_end_matches(';')).is_ok() {
+			println!("{}", commands::set(&mut self.runtime.borrow_mut(), cmd)?);
+			return Ok(());
+		}
+		let runtime = self.runtime.borrow();
+		let res_eq = runtime.run(cmd)?.un_nest();
 		let res_eq_str = format!("{}", res_eq);
 		let res_val: Value =
-			self.runtime.math_to_value(res_eq, &RuntimeContext::new())?;
+			runtime.math_to_value(res_eq, &RuntimeContext::new())?;
 		if res_eq_str == format!("{}", res_val) {
 			println!("{}", res_val);
 		} else {
 			println!("{} = {}", res_eq_str, res_val);
 		}
+		// random choices aren't part of the expression, so show how they were made
+		for draw in runtime.draws.borrow().iter() {
+			println!("{}", draw);
+		}
 		Ok(())
 	}
 
@@ -39,6 +54,7 @@ impl<'a> App<'a> {
 		rl.set_helper(Some(RuntimeHinter(self.runtime)));
 		let zinger = self
 			.runtime
+			.borrow()
 			.mod_loader
 			.zinger()
 			.unwrap_or("ivory".to_string());
@@ -69,6 +85,33 @@ impl<'a> App<'a> {
 	}
 }
 
+/// Checks the types in a file without running it, and returns the exit code
+fn check(file: &str, strict: bool) -> i32 {
+	let mut runtime = Runtime::new(rand::thread_rng(), FileLoader::new());
+	runtime.strict = strict;
+	let filename = Path::new(file).file_name().unwrap().to_str().unwrap();
+	if let Err(err) = runtime.load_path(filename, file) {
+		println!("{}", format!("{}", err).red());
+		return 1;
+	}
+	let diagnostics = runtime.check();
+	let warnings = diagnostics.iter().filter(|d| d.is_warning()).count();
+	let errors = diagnostics.len() - warnings;
+	for diagnostic in diagnostics.iter() {
+		if diagnostic.is_warning() {
+			println!("{} {}", "warning:".yellow(), diagnostic);
+		} else {
+			println!("{} {}", "error:".red(), diagnostic);
+		}
+	}
+	println!("{} errors, {} warnings", errors, warnings);
+	if errors > 0 {
+		1
+	} else {
+		0
+	}
+}
+
 fn main() {
 	#[cfg(target_os = "windows")]
 	if ansi_term::enable_ansi_support().is_err() {
@@ -88,12 +131,33 @@ fn main() {
 				.help("Run a single command and exit")
 				.takes_value(true),
 		)
+		.arg(
+			Arg::with_name("STRICT")
+				.long("strict")
+				.help("Don't turn values into strings implicitly")
+				.global(true),
+		)
+		.subcommand(
+			SubCommand::with_name("check")
+				.about("Check a file for type errors without running it")
+				.arg(
+					Arg::with_name("FILE")
+						.help("A file path or url to check")
+						.required(true),
+				),
+		)
 		.get_matches();
 
+	let strict = matches.is_present("STRICT");
+	if let Some(matches) = matches.subcommand_matches("check") {
+		std::process::exit(check(matches.value_of("FILE").unwrap(), strict));
+	}
+
 	let file = matches.value_of("FILE");
 	let run = matches.value_of("RUN");
 
 	let mut runtime = Runtime::new(rand::thread_rng(), FileLoader::new());
+	runtime.strict = strict;
 
 	if let Some(file) = file {
 		let filename = Path::new(file).file_name().unwrap().to_str().unwrap();
@@ -102,6 +166,7 @@ fn main() {
 			.expect("Unable to load file");
 	}
 
+	let runtime = RefCell::new(runtime);
 	let mut app = App { runtime: &runtime };
 	if let Some(run) = run {
 		app.run(run).expect("error running expression");
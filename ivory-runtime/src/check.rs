@@ -0,0 +1,1271 @@
+use std::{
+	cell::RefCell, collections::HashMap, fmt::Display, iter::Peekable,
+	vec::IntoIter,
+};
+
+use ivory_expression::{Expression, ExpressionComponent, Pair};
+use ivory_tokenizer::{
+	accessor::{Accessor, AccessorComponent, AccessorRoot},
+	expression::{
+		logic::Comparator,
+		math::{ExprOpMath, ExprOpMathKind},
+		ExpressionToken, Op,
+	},
+	istruct::StructDefinitionValue,
+	itype::Type,
+	values::{
+		array::ArrayValue,
+		function::FunctionValue,
+		match_value::{MatchPattern, MatchValue},
+		object::ObjectValue,
+		struct_instance::StructInstance,
+		Value,
+	},
+	variable::Variable,
+};
+
+use crate::{
+	enum_value::missing_variants,
+	error::TypeProblem,
+	expr::Precedence,
+	graph::as_function,
+	math::MathLibrary,
+	prec::Assoc,
+	runtime::{Runtime, RuntimeValues},
+	std_fns::StdFnLibrary,
+	table,
+	value::type_fits,
+	RuntimeError,
+};
+
+/// A problem found while checking, and the definition it was found in.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+	/// A variable name, a struct name, or `Struct.method`
+	pub location: String,
+	pub problem: TypeProblem,
+}
+
+impl Diagnostic {
+	/// Warnings are for code that runs, but probably doesn't do what was meant
+	pub fn is_warning(&self) -> bool {
+		matches!(
+			self.problem,
+			TypeProblem::Coercion(..) | TypeProblem::MaybeNone(_)
+		)
+	}
+}
+
+impl Display for Diagnostic {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}: {}", self.location, self.problem)
+	}
+}
+
+/// Types of the names that can be used in an expression, like the parameters
+/// of a function.
+type Scope = HashMap<String, Type>;
+
+/// A field of a struct, and whether it has to be given when creating one.
+type Field = (String, Type, bool);
+
+/// Infers the types of everything in loaded modules without evaluating them,
+/// so no dice are rolled. Anything that can't be known ahead of time is
+/// `any`, and is left for the runtime to check.
+pub struct Checker<'a> {
+	values: &'a RuntimeValues,
+	std_fns: &'a StdFnLibrary,
+	math: &'a MathLibrary,
+	/// Same as the runtime's strict mode, where strings aren't made implicitly
+	strict: bool,
+	/// Inferred types of variables, and of methods as `Struct.method`
+	types: RefCell<HashMap<String, Type>>,
+	structs: RefCell<HashMap<String, Option<Vec<Field>>>>,
+	/// The definitions being inferred right now, innermost last
+	stack: RefCell<Vec<String>>,
+	diagnostics: RefCell<Vec<Diagnostic>>,
+}
+
+/// Pending calls, like in the runtime
+enum PendingCall {
+	/// A standard function, and the type of the value it's called on
+	Std(String, Type),
+	Method(String, Type),
+	/// A function defined in a module, and the type of the value it's called
+	/// on, which is its first argument
+	Function(String, Type, Type),
+}
+
+impl<'a> Checker<'a> {
+	pub fn new(runtime: &'a Runtime) -> Self {
+		Self {
+			values: &runtime.values,
+			std_fns: &runtime.std_fns,
+			math: &runtime.math,
+			strict: runtime.strict,
+			types: RefCell::new(HashMap::new()),
+			structs: RefCell::new(HashMap::new()),
+			stack: RefCell::new(Vec::new()),
+			diagnostics: RefCell::new(Vec::new()),
+		}
+	}
+
+	/// Checks every variable, struct and method of the root module.
+	pub fn check(self) -> Vec<Diagnostic> {
+		for name in self.values.variables.keys() {
+			self.variable(name);
+		}
+		for name in self.values.structs.keys() {
+			self.struct_fields(name);
+		}
+		for (struct_name, methods) in self.values.methods.iter() {
+			for method in methods.values() {
+				self.method(struct_name, method);
+			}
+		}
+		self.diagnostics.into_inner()
+	}
+
+	/// The type of a variable, or `any` if it can't be inferred.
+	pub fn variable(&self, name: &str) -> Type {
+		match self.values.get_variable(name) {
+			Some(variable) => self.memo(name.to_string(), || {
+				self.expression(&variable.value, &Scope::new())
+			}),
+			None => {
+				self.report(TypeProblem::UnknownVariable(name.to_string()));
+				Type::Any
+			}
+		}
+	}
+
+	/// The type of a method, with `self` being the struct it's defined on.
+	fn method(&self, struct_name: &str, method: &Variable) -> Type {
+		self.memo(format!("{}.{}", struct_name, method.name), || {
+			let scope = Scope::from([(
+				"self".to_string(),
+				Type::Struct(ivory_tokenizer::istruct::StructName(
+					struct_name.to_string(),
+				)),
+			)]);
+			match as_function(&method.value) {
+				Some(f) => self.function(f, scope),
+				None => self.expression(&method.value, &scope),
+			}
+		})
+	}
+
+	/// Infers something once, keeping track of where problems are found.
+	fn memo<F: FnOnce() -> Type>(&self, location: String, f: F) -> Type {
+		if let Some(t) = self.types.borrow().get(&location) {
+			return t.clone();
+		}
+		// recursive definitions can't be inferred, so they're left to the runtime
+		if self.stack.borrow().contains(&location) {
+			return Type::Any;
+		}
+		self.stack.borrow_mut().push(location.clone());
+		let t = f();
+		self.stack.borrow_mut().pop();
+		self.types.borrow_mut().insert(location, t.clone());
+		t
+	}
+
+	fn report(&self, problem: TypeProblem) {
+		let location = self.stack.borrow().last().cloned().unwrap_or_default();
+		self
+			.diagnostics
+			.borrow_mut()
+			.push(Diagnostic { location, problem });
+	}
+
+	/// Every field of a struct with its type. Fields with a default value have
+	/// the type of that value, which can use the fields before it.
+	fn struct_fields(&self, name: &str) -> Option<Vec<Field>> {
+		if let Some(fields) = self.structs.borrow().get(name) {
+			return fields.clone();
+		}
+		if self.stack.borrow().iter().any(|s| s == name) {
+			return None;
+		}
+		let definition = match self.values.struct_fields(name) {
+			Ok(definition) => definition,
+			Err(_) => {
+				self.report(TypeProblem::StructNotFound(name.to_string()));
+				return None;
+			}
+		};
+		self.stack.borrow_mut().push(name.to_string());
+		let mut scope: Scope = definition
+			.iter()
+			.filter_map(|(field, value)| match value {
+				StructDefinitionValue::Type(t) => {
+					Some((field.0.clone(), self.resolve(t.clone())))
+				}
+				StructDefinitionValue::Value(_) => None,
+			})
+			.collect();
+		let mut fields = Vec::new();
+		for (field, value) in definition.iter() {
+			let field = field.0.clone();
+			match value {
+				StructDefinitionValue::Type(t) => {
+					let required = !matches!(t, Type::Optional(_));
+					fields.push((field, self.resolve(t.clone()), required))
+				}
+				StructDefinitionValue::Value(expr) => {
+					let t = self.expression(expr, &scope);
+					scope.insert(field.clone(), t.clone());
+					fields.push((field, t, false));
+				}
+			}
+		}
+		self.stack.borrow_mut().pop();
+		self
+			.structs
+			.borrow_mut()
+			.insert(name.to_string(), Some(fields.clone()));
+		Some(fields)
+	}
+
+	/// Named types are parsed as structs, so the ones naming enums are turned
+	/// into enums
+	fn resolve(&self, t: Type) -> Type {
+		match t {
+			Type::Struct(name) if self.values.enum_definition(&name.0).is_some() => {
+				Type::Enum(name)
+			}
+			Type::Array(t) => Type::Array(Box::new(self.resolve(*t))),
+			Type::Optional(t) => Type::Optional(Box::new(self.resolve(*t))),
+			Type::Function(params, ret) => Type::Function(
+				params.into_iter().map(|p| self.resolve(p)).collect(),
+				Box::new(self.resolve(*ret)),
+			),
+			t => t,
+		}
+	}
+
+	fn fits(&self, t: &Type, expected: &Type) -> bool {
+		type_fits(t, expected, self.values)
+	}
+
+	/// The most specific type both types fit into
+	fn common(&self, a: Type, b: Type) -> Type {
+		if let (Type::Optional(a), Type::Optional(b)) = (&a, &b) {
+			return Type::Optional(Box::new(self.common(*a.clone(), *b.clone())));
+		}
+		if let (Type::Optional(a), b) | (b, Type::Optional(a)) = (&a, &b) {
+			return Type::Optional(Box::new(self.common(*a.clone(), b.clone())));
+		}
+		if self.fits(&a, &b) {
+			b
+		} else if self.fits(&b, &a) {
+			a
+		} else {
+			Type::Any
+		}
+	}
+
+	fn expression(
+		&self,
+		expr: &Expression<Op, ExpressionToken>,
+		scope: &Scope,
+	) -> Type {
+		// dice are rolled first, then dice operators are applied, then
+		// everything else runs in order of precedence, just like the runtime
+		let mut operands = vec![self.component(&expr.first, scope)];
+		let mut ops = Vec::new();
+		for Pair(op, component) in expr.pairs.iter() {
+			let rhs = self.component(component, scope);
+			if let Op::Dice = op {
+				let lhs = operands.pop().unwrap();
+				operands.push(self.dice(lhs, op, rhs));
+			} else {
+				ops.push(op);
+				operands.push(rhs);
+			}
+		}
+		let mut operands = operands.into_iter();
+		let mut first = operands.next().unwrap();
+		let mut rest: Vec<(&Op, Type)> = Vec::new();
+		for (op, rhs) in ops.into_iter().zip(operands) {
+			if let Op::DiceOp(_) = op {
+				let lhs = match rest.last_mut() {
+					Some((_, lhs)) => lhs,
+					None => &mut first,
+				};
+				*lhs = self.dice_op(lhs.clone(), op, rhs);
+			} else {
+				rest.push((op, rhs));
+			}
+		}
+		self.climb(first, 0, &mut rest.into_iter().peekable(), scope)
+	}
+
+	fn climb(
+		&self,
+		mut lhs: Type,
+		min_prec: usize,
+		ops: &mut Peekable<IntoIter<(&Op, Type)>>,
+		scope: &Scope,
+	) -> Type {
+		while let Some((op, _)) = ops.peek() {
+			let (prec, _) = Precedence::from(*op).rule();
+			if prec < min_prec {
+				break;
+			}
+			let (op, mut rhs) = ops.next().unwrap();
+			while let Some((peek, _)) = ops.peek() {
+				let (peek_prec, peek_assoc) = Precedence::from(*peek).rule();
+				if peek_prec > prec || peek_assoc == Assoc::Right && peek_prec == prec {
+					rhs = self.climb(rhs, peek_prec, ops, scope);
+				} else {
+					break;
+				}
+			}
+			lhs = self.binary(lhs, op, rhs, scope);
+		}
+		lhs
+	}
+
+	fn component(
+		&self,
+		component: &ExpressionComponent<Op, ExpressionToken>,
+		scope: &Scope,
+	) -> Type {
+		match component {
+			ExpressionComponent::Token(token) => self.token(token, scope),
+			ExpressionComponent::Paren(expr) => self.expression(expr, scope),
+		}
+	}
+
+	fn dice(&self, count: Type, op: &Op, sides: Type) -> Type {
+		if !is_number(&count) || !is_number(&sides) {
+			self.report(TypeProblem::CannotRunOp(count, op.clone(), sides));
+		}
+		Type::Roll
+	}
+
+	fn dice_op(&self, roll: Type, op: &Op, rhs: Type) -> Type {
+		if !matches!(roll, Type::Roll | Type::Any) || !is_number(&rhs) {
+			self.report(TypeProblem::CannotRunOp(roll, op.clone(), rhs));
+		}
+		Type::Roll
+	}
+
+	/// Mirrors `Value::run_op`
+	fn binary(&self, lhs: Type, op: &Op, rhs: Type, scope: &Scope) -> Type {
+		use Type::*;
+		let (lhs, rhs) = match op {
+			Op::Math(ExprOpMath::Ternary(_))
+			| Op::Comparator(Comparator::Eq)
+			| Op::Coalesce => (lhs, rhs),
+			_ => (self.unwrap_optional(lhs), self.unwrap_optional(rhs)),
+		};
+		let adding = matches!(
+			op,
+			Op::Math(ExprOpMath::Binary {
+				kind: ExprOpMathKind::Add,
+				..
+			})
+		);
+		match (op, &lhs, &rhs) {
+			(Op::Math(ExprOpMath::Ternary(then)), _, _) => {
+				let then = self.expression(then, scope);
+				self.common(then, rhs)
+			}
+			(Op::Comparator(Comparator::Eq), _, _) => Boolean,
+			(Op::Coalesce, Optional(t), _) => self.common(*t.clone(), rhs),
+			// the default is never used
+			(Op::Coalesce, _, _) => lhs,
+			(_, Any, _) | (_, _, Any) => Any,
+			(_, String, t) | (_, t, String)
+				if adding && is_number(t) && !self.strict =>
+			{
+				if matches!(t, Roll | Boolean) {
+					self.report(TypeProblem::Coercion(
+						lhs.clone(),
+						op.clone(),
+						rhs.clone(),
+					));
+				}
+				String
+			}
+			(_, String, String) if adding => String,
+			(Op::Math(ExprOpMath::Binary { kind, round }), l, r)
+				if is_number(l) && is_number(r) =>
+			{
+				if matches!(l, Boolean) || matches!(r, Boolean) {
+					self.report(TypeProblem::Coercion(
+						lhs.clone(),
+						op.clone(),
+						rhs.clone(),
+					));
+				}
+				match (kind, round) {
+					_ if matches!(l, Decimal) || matches!(r, Decimal) => Decimal,
+					(ExprOpMathKind::Div, None) => Decimal,
+					_ => Integer,
+				}
+			}
+			(Op::Comparator(_), l, r) if is_number(l) && is_number(r) => Boolean,
+			(_, Array(a), Array(b)) if adding => {
+				Array(Box::new(self.common(*a.clone(), *b.clone())))
+			}
+			(_, Array(a), t) | (_, t, Array(a)) if adding => {
+				Array(Box::new(self.common(*a.clone(), t.clone())))
+			}
+			_ => {
+				self.report(TypeProblem::CannotRunOp(lhs, op.clone(), rhs));
+				Any
+			}
+		}
+	}
+
+	fn token(
+		&self,
+		ExpressionToken(Accessor(root, components)): &ExpressionToken,
+		scope: &Scope,
+	) -> Type {
+		let mut components = components.iter();
+		let mut t = match root {
+			AccessorRoot::Variable(name) => match scope.get(&name.0) {
+				Some(t) => t.clone(),
+				None
+					if self.values.get_variable(&name.0).is_none()
+						&& self.std_fns.has(&name.0) =>
+				{
+					self.free_std_call(&name.0, components.next(), scope)
+				}
+				None
+					if self.values.get_variable(&name.0).is_none()
+						&& self.values.enum_definition(&name.0).is_some() =>
+				{
+					self.enum_variant(&name.0, &mut components, scope)
+				}
+				None
+					if name.0 == "math"
+						&& self.values.get_variable(&name.0).is_none() =>
+				{
+					self.math_namespace(&mut components, scope)
+				}
+				None
+					if table::is_roll(components.as_slice())
+						&& self.values.table(&name.0).is_some() =>
+				{
+					components.nth(1);
+					Type::Object
+				}
+				None => self.variable(&name.0),
+			},
+			AccessorRoot::Value(value) => self.value(value, scope),
+		};
+		let mut pending_call: Option<PendingCall> = None;
+		// set once `?.` is used on something that might be none
+		let mut optional = false;
+		for component in components {
+			if let Type::Optional(inner) = &t {
+				if let AccessorComponent::OptionalProperty(_) = component {
+					optional = true;
+					t = *inner.clone();
+				} else {
+					t = self.unwrap_optional(t);
+				}
+			}
+			if let Some(pending) = &pending_call {
+				if !matches!(component, AccessorComponent::Call(_)) {
+					self.report(uncalled(pending));
+					pending_call = None;
+					t = Type::Any;
+				}
+			}
+			match component {
+				AccessorComponent::Property(prop)
+				| AccessorComponent::OptionalProperty(prop) => {
+					t = match &t {
+						Type::Struct(name) => {
+							let field = self.struct_fields(&name.0).and_then(|fields| {
+								fields
+									.into_iter()
+									.find(|(field, ..)| field == &prop.0)
+									.map(|(_, t, _)| t)
+							});
+							if let Some(field) = field {
+								field
+							} else if let Some((defined_on, method)) =
+								self.find_method(&name.0, &prop.0)
+							{
+								let method = self.method(&defined_on, method);
+								pending_call =
+									Some(PendingCall::Method(prop.0.clone(), method));
+								Type::Any
+							} else if self.values.function(&prop.0).is_some() {
+								pending_call = Some(self.function_call(&prop.0, &t));
+								Type::Any
+							} else if self.std_fns.has(&prop.0) {
+								pending_call =
+									Some(PendingCall::Std(prop.0.clone(), t.clone()));
+								Type::Any
+							} else {
+								self.report(TypeProblem::NoProperty(t.clone(), prop.0.clone()));
+								Type::Any
+							}
+						}
+						// objects can have any field
+						Type::Object | Type::Any => Type::Any,
+						_ if self.values.function(&prop.0).is_some() => {
+							pending_call = Some(self.function_call(&prop.0, &t));
+							Type::Any
+						}
+						_ if self.std_fns.has(&prop.0) => {
+							pending_call = Some(PendingCall::Std(prop.0.clone(), t.clone()));
+							Type::Any
+						}
+						_ => {
+							self.report(TypeProblem::NoProperty(t.clone(), prop.0.clone()));
+							Type::Any
+						}
+					};
+				}
+				AccessorComponent::Index(i) => {
+					self.expression(i, scope);
+					t = match t {
+						Type::Array(t) => *t,
+						Type::String => Type::String,
+						Type::Roll => Type::Integer,
+						Type::Object | Type::Struct(_) | Type::Any => Type::Any,
+						t => {
+							self.report(TypeProblem::CannotIndex(t));
+							Type::Any
+						}
+					};
+				}
+				AccessorComponent::Call(args) => {
+					let args: Vec<Type> =
+						args.iter().map(|arg| self.expression(arg, scope)).collect();
+					t = match pending_call.take() {
+						Some(PendingCall::Std(name, receiver)) => {
+							self.std_call(&name, Some(receiver), args)
+						}
+						// methods that aren't functions are still called to get them
+						Some(PendingCall::Method(_, f @ Type::Function(..))) => {
+							self.call(f, args)
+						}
+						Some(PendingCall::Method(_, t)) => {
+							if !args.is_empty() {
+								self.report(TypeProblem::WrongArgumentCount(0, args.len()));
+							}
+							t
+						}
+						Some(PendingCall::Function(_, f, receiver)) => {
+							self.call(f, [vec![receiver], args].concat())
+						}
+						None => self.call(t, args),
+					};
+				}
+			}
+		}
+		if let Some(pending) = &pending_call {
+			self.report(uncalled(pending));
+			t = Type::Any;
+		}
+		if optional && !matches!(t, Type::Optional(_)) {
+			t = Type::Optional(Box::new(t));
+		}
+		t
+	}
+
+	/// Using something that might be none as if it isn't works until it is
+	fn unwrap_optional(&self, t: Type) -> Type {
+		match t {
+			Type::Optional(inner) => {
+				self.report(TypeProblem::MaybeNone(Type::Optional(inner.clone())));
+				*inner
+			}
+			t => t,
+		}
+	}
+
+	/// A variant of an enum, like `Shape.Circle(2)`
+	fn enum_variant(
+		&self,
+		name: &str,
+		components: &mut std::slice::Iter<AccessorComponent>,
+		scope: &Scope,
+	) -> Type {
+		let def = match self.values.enum_definition(name) {
+			Some(def) => def,
+			None => {
+				self.report(TypeProblem::UnknownVariable(name.to_string()));
+				return Type::Any;
+			}
+		};
+		let variant = match components.next() {
+			Some(AccessorComponent::Property(variant)) => {
+				match def.variant(&variant.0) {
+					Some(variant) => variant,
+					None => {
+						self.report(TypeProblem::UnknownVariant(
+							name.to_string(),
+							variant.0.clone(),
+						));
+						return Type::Any;
+					}
+				}
+			}
+			_ => {
+				self.report(TypeProblem::UnknownVariable(name.to_string()));
+				return Type::Any;
+			}
+		};
+		if !variant.payload.is_empty() {
+			let args = match components.next() {
+				Some(AccessorComponent::Call(args)) => args.as_slice(),
+				_ => &[],
+			};
+			if args.len() != variant.payload.len() {
+				self.report(TypeProblem::WrongPayload(
+					variant.name.0.clone(),
+					variant.payload.len(),
+					args.len(),
+				));
+			}
+			for (i, (arg, t)) in args.iter().zip(variant.payload.iter()).enumerate() {
+				let arg = self.expression(arg, scope);
+				let t = self.resolve(t.clone());
+				if !self.fits(&arg, &t) {
+					self.report(TypeProblem::WrongArgumentType(i + 1, t, arg));
+				}
+			}
+		}
+		Type::Enum(def.name.clone())
+	}
+
+	/// A constant like `math.pi`, or a call like `math.sqrt(x)`
+	fn math_namespace(
+		&self,
+		components: &mut std::slice::Iter<AccessorComponent>,
+		scope: &Scope,
+	) -> Type {
+		let name = match components.as_slice().first() {
+			Some(AccessorComponent::Property(name)) => {
+				components.next();
+				&name.0
+			}
+			// `math` on its own is a value, used like an object
+			_ => return Type::Object,
+		};
+		if let Some(constant) = self.math.constants.get(name) {
+			return constant.val_type();
+		}
+		if !self.math.has(name) {
+			self.report(TypeProblem::NoProperty(
+				Type::Object,
+				format!("math.{}", name),
+			));
+			return Type::Any;
+		}
+		match components.next() {
+			Some(AccessorComponent::Call(args)) => {
+				for (i, arg) in args.iter().enumerate() {
+					let t = self.expression(arg, scope);
+					if !is_number(&t) {
+						self.report(TypeProblem::WrongArgumentType(
+							i + 1,
+							Type::Decimal,
+							t,
+						));
+					}
+				}
+				match name.as_str() {
+					"sqrt" | "log" => Type::Decimal,
+					"floor" | "ceil" | "round" | "sign" => Type::Integer,
+					_ => Type::Any,
+				}
+			}
+			_ => {
+				self.report(TypeProblem::NotCalled(format!("math.{}", name)));
+				Type::Any
+			}
+		}
+	}
+
+	/// A standard function called like `int(x)`
+	fn free_std_call(
+		&self,
+		name: &str,
+		component: Option<&AccessorComponent>,
+		scope: &Scope,
+	) -> Type {
+		match component {
+			Some(AccessorComponent::Call(args))
+				if !args.is_empty() || self.std_fns.native.contains_key(name) =>
+			{
+				let args = args.iter().map(|arg| self.expression(arg, scope)).collect();
+				self.std_call(name, None, args)
+			}
+			_ => {
+				self.report(TypeProblem::NotCalled(name.to_string()));
+				Type::Any
+			}
+		}
+	}
+
+	/// What a standard function returns. Functions added with
+	/// `Runtime::register_fn` declare their types, so their arguments are
+	/// checked too.
+	fn std_call(
+		&self,
+		name: &str,
+		receiver: Option<Type>,
+		args: Vec<Type>,
+	) -> Type {
+		match self.std_fns.native.get(name) {
+			Some(native) => {
+				let args = receiver.into_iter().chain(args).collect();
+				let f =
+					Type::Function(native.params.clone(), Box::new(native.ret.clone()));
+				self.call(self.resolve(f), args)
+			}
+			None => self
+				.std_fns
+				.fns
+				.get(name)
+				.map_or(Type::Any, |f| f.ret.clone()),
+		}
+	}
+
+	/// A function defined in a module, called on a value of type `receiver`
+	fn function_call(&self, name: &str, receiver: &Type) -> PendingCall {
+		PendingCall::Function(
+			name.to_string(),
+			self.variable(name),
+			receiver.clone(),
+		)
+	}
+
+	fn call(&self, f: Type, args: Vec<Type>) -> Type {
+		match f {
+			Type::Function(params, ret) => {
+				if args.len() != params.len() {
+					self
+						.report(TypeProblem::WrongArgumentCount(params.len(), args.len()));
+				}
+				for (i, (param, arg)) in params.into_iter().zip(args).enumerate() {
+					if !self.fits(&arg, &param) {
+						self.report(TypeProblem::WrongArgumentType(i + 1, param, arg));
+					}
+				}
+				*ret
+			}
+			Type::Any => Type::Any,
+			t => {
+				self.report(TypeProblem::CannotCall(t));
+				Type::Any
+			}
+		}
+	}
+
+	/// The struct a method is defined on, and the method
+	fn find_method(
+		&self,
+		struct_name: &str,
+		name: &str,
+	) -> Option<(String, &'a Variable)> {
+		let mut struct_name = struct_name.to_string();
+		loop {
+			if let Some(method) = self
+				.values
+				.methods
+				.get(&struct_name)
+				.and_then(|methods| methods.get(name))
+			{
+				return Some((struct_name, method));
+			}
+			struct_name = self
+				.values
+				.structs
+				.get(&struct_name)?
+				.parent
+				.as_ref()?
+				.0
+				.clone();
+		}
+	}
+
+	fn value(&self, value: &Value, scope: &Scope) -> Type {
+		match value {
+			Value::Boolean(_) => Type::Boolean,
+			Value::Decimal(_) => Type::Decimal,
+			Value::Integer(_) => Type::Integer,
+			Value::String(_) => Type::String,
+			Value::None(_) => Type::Optional(Box::new(Type::Any)),
+			Value::Array(ArrayValue(values)) => {
+				let mut types = values.iter().map(|v| self.expression(v, scope));
+				let first = types.next().unwrap_or(Type::Any);
+				Type::Array(Box::new(types.fold(first, |a, b| self.common(a, b))))
+			}
+			Value::Object(ObjectValue(values)) => {
+				for value in values.values() {
+					self.expression(value, scope);
+				}
+				Type::Object
+			}
+			Value::Struct(instance) => self.struct_instance(instance, scope),
+			Value::Match(m) => self.match_value(m, scope),
+			// functions can't see anything from where they're defined
+			Value::Function(f) => self.function(f, Scope::new()),
+		}
+	}
+
+	/// The type every arm of a match fits into. Values held by variants have
+	/// the types they're defined with.
+	fn match_value(&self, m: &MatchValue, scope: &Scope) -> Type {
+		let def = match self.unwrap_optional(self.expression(&m.value, scope)) {
+			Type::Enum(name) => self.values.enum_definition(&name.0),
+			Type::Any => None,
+			t => {
+				self.report(TypeProblem::CannotMatch(t));
+				None
+			}
+		};
+		if let Some(def) = def {
+			match missing_variants(def, &m.arms) {
+				Ok(missing) if !missing.is_empty() => self
+					.report(TypeProblem::NonExhaustiveMatch(def.name.0.clone(), missing)),
+				Err(RuntimeError::UnknownVariant(name, variant)) => {
+					self.report(TypeProblem::UnknownVariant(name, variant))
+				}
+				Err(RuntimeError::WrongPayload(variant, expected, got)) => {
+					self.report(TypeProblem::WrongPayload(variant, expected, got))
+				}
+				_ => {}
+			}
+		}
+		let mut arms = m.arms.iter().map(|arm| {
+			let mut scope = scope.clone();
+			if let MatchPattern::Variant(name, bindings) = &arm.pattern {
+				let variant = def.and_then(|def| def.variant(&name.0));
+				for (i, binding) in bindings.iter().enumerate() {
+					let t = variant
+						.and_then(|variant| variant.payload.get(i))
+						.map(|t| self.resolve(t.clone()))
+						.unwrap_or(Type::Any);
+					scope.insert(binding.0.clone(), t);
+				}
+			}
+			self.expression(&arm.expr, &scope)
+		});
+		let first = arms.next().unwrap_or(Type::Any);
+		arms.fold(first, |a, b| self.common(a, b))
+	}
+
+	fn function(&self, f: &FunctionValue, mut scope: Scope) -> Type {
+		let params: Vec<Type> = f
+			.arg_types
+			.iter()
+			.map(|t| self.resolve(t.clone().unwrap_or(Type::Any)))
+			.collect();
+		for (name, t) in f.args.iter().zip(params.iter()) {
+			scope.insert(name.0.clone(), t.clone());
+		}
+		let body = self.expression(&f.expr, &scope);
+		let ret = match &f.ret {
+			Some(ret) => {
+				let ret = self.resolve(ret.clone());
+				if !self.fits(&body, &ret) {
+					self.report(TypeProblem::WrongReturnType(ret.clone(), body));
+				}
+				ret
+			}
+			None => body,
+		};
+		Type::Function(params, Box::new(ret))
+	}
+
+	fn struct_instance(&self, instance: &StructInstance, scope: &Scope) -> Type {
+		let name = &instance.name.0;
+		let fields = self.struct_fields(name);
+		for (field, value) in instance.values.iter() {
+			let t = self.expression(value, scope);
+			let fields = match &fields {
+				Some(fields) => fields,
+				None => continue,
+			};
+			match fields.iter().find(|(f, ..)| f == &field.0) {
+				Some((_, expected, true)) if !self.fits(&t, expected) => {
+					self.report(TypeProblem::WrongFieldType(
+						field.0.clone(),
+						expected.clone(),
+						t,
+					));
+				}
+				Some(_) => {}
+				None => self
+					.report(TypeProblem::FieldNotOnStruct(name.clone(), field.0.clone())),
+			}
+		}
+		for (field, t, required) in fields.iter().flatten() {
+			if *required && !instance.values.keys().any(|f| &f.0 == field) {
+				self.report(TypeProblem::MissingField(
+					name.clone(),
+					field.clone(),
+					t.clone(),
+				));
+			}
+		}
+		Type::Struct(instance.name.clone())
+	}
+}
+
+/// Types that the runtime turns into numbers for math
+fn is_number(t: &Type) -> bool {
+	matches!(
+		t,
+		Type::Integer | Type::Decimal | Type::Boolean | Type::Roll | Type::Any
+	)
+}
+
+fn uncalled(pending: &PendingCall) -> TypeProblem {
+	match pending {
+		PendingCall::Std(name, _)
+		| PendingCall::Method(name, _)
+		| PendingCall::Function(name, ..) => TypeProblem::NotCalled(name.clone()),
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::{runtime::Runtime, value::Value as RuntimeValue};
+	use ivory_tokenizer::{istruct::StructName, Parse};
+
+	fn runtime(src: &str) -> Runtime {
+		let mut runtime = Runtime::new(rand::thread_rng(), ());
+		runtime.load(src, "").unwrap();
+		runtime
+	}
+
+	#[test]
+	fn infer_types() {
+		let runtime = runtime(
+			r#"
+			str = 15 + 1;
+			half = str / 2;
+			floored = str /_ 2;
+			scale = 1.5 * str;
+			check = 1d20 + str;
+			dc = 1d20 r<= 2;
+			passed = check >= 10;
+			name = "Ser " + "Bors";
+			scores = [1, 2, 3] + 4;
+			mixed = [1, "a"];
+			double = (a: int): int -> a * 2;
+			doubled = double(str);
+			untyped = a -> a;
+			first = scores[0];
+			pick = str > 10 ? 1 : 2.5;
+			"#,
+		);
+		let checker = Checker::new(&runtime);
+		let types = [
+			("str", Type::Integer),
+			("half", Type::Decimal),
+			("floored", Type::Integer),
+			("scale", Type::Decimal),
+			("check", Type::Integer),
+			("dc", Type::Roll),
+			("passed", Type::Boolean),
+			("name", Type::String),
+			("scores", Type::Array(Box::new(Type::Integer))),
+			("mixed", Type::Array(Box::new(Type::Any))),
+			("double", Type::parse("(int) -> int").unwrap().1),
+			("doubled", Type::Integer),
+			("untyped", Type::parse("(any) -> any").unwrap().1),
+			("first", Type::Integer),
+			("pick", Type::Decimal),
+		];
+		for (name, t) in types {
+			assert_eq!(checker.variable(name), t, "type of {}", name);
+		}
+		assert!(runtime.check().is_empty());
+	}
+
+	#[test]
+	fn struct_types() {
+		let runtime = runtime(
+			r#"
+			struct Item {
+				name: string,
+				weight: int,
+				heavy: weight > 10
+			}
+
+			impl Item {
+				label = self.name + "!";
+				carry = (strength: int) -> strength * 15 >= self.weight;
+			}
+
+			sword = Item { name: "Sword", weight: 3 };
+			label = sword.label();
+			carry = sword.carry(10);
+			heavy = sword.heavy;
+			"#,
+		);
+		let checker = Checker::new(&runtime);
+		assert_eq!(
+			checker.variable("sword"),
+			Type::Struct(StructName("Item".to_string()))
+		);
+		assert_eq!(checker.variable("label"), Type::String);
+		assert_eq!(checker.variable("carry"), Type::Boolean);
+		assert_eq!(checker.variable("heavy"), Type::Boolean);
+		assert!(runtime.check().is_empty());
+	}
+
+	#[test]
+	fn find_problems() {
+		let runtime = runtime(
+			r#"
+			struct Item {
+				name: string,
+				weight: int
+			}
+
+			impl Item {
+				bad = self.name * 2;
+			}
+
+			double = (a: int): int -> a * 2;
+			halve = (a: int): int -> a / 2;
+			bad_arg = double("two");
+			bad_op = [1] * 2;
+			bad_call = 5(1);
+			bad_field = Item { name: "Sword", weight: "heavy" };
+			missing = Item { name: "Sword" };
+			unknown = nothing + 1;
+			roll_text = "You rolled " + 1d20;
+			"#,
+		);
+		let diagnostics = runtime.check();
+		let found = |location: &str, f: fn(&TypeProblem) -> bool| {
+			diagnostics
+				.iter()
+				.any(|d| d.location == location && f(&d.problem))
+		};
+		assert!(found("bad_arg", |p| matches!(
+			p,
+			TypeProblem::WrongArgumentType(1, Type::Integer, Type::String)
+		)));
+		assert!(found("halve", |p| matches!(
+			p,
+			TypeProblem::WrongReturnType(Type::Integer, Type::Decimal)
+		)));
+		assert!(found("bad_op", |p| matches!(
+			p,
+			TypeProblem::CannotRunOp(..)
+		)));
+		assert!(found("bad_call", |p| matches!(
+			p,
+			TypeProblem::CannotCall(_)
+		)));
+		assert!(found("bad_field", |p| matches!(
+			p,
+			TypeProblem::WrongFieldType(field, Type::Integer, Type::String)
+				if field == "weight"
+		)));
+		assert!(found("missing", |p| matches!(
+			p,
+			TypeProblem::MissingField(..)
+		)));
+		assert!(found("unknown", |p| matches!(
+			p,
+			TypeProblem::UnknownVariable(name) if name == "nothing"
+		)));
+		assert!(found("Item.bad", |p| matches!(
+			p,
+			TypeProblem::CannotRunOp(..)
+		)));
+		let warnings: Vec<_> =
+			diagnostics.iter().filter(|d| d.is_warning()).collect();
+		assert_eq!(warnings.len(), 1);
+		assert_eq!(warnings[0].location, "roll_text");
+		assert_eq!(diagnostics.len(), 9);
+	}
+
+	#[test]
+	fn casts() {
+		let mut runtime = runtime(
+			r#"
+			level = 5;
+			as_int = "12".int();
+			as_decimal = decimal(level);
+			text = "Level " + level.string();
+			implicit = "Level " + level;
+			"#,
+		);
+		let checker = Checker::new(&runtime);
+		assert_eq!(checker.variable("as_int"), Type::Integer);
+		assert_eq!(checker.variable("as_decimal"), Type::Decimal);
+		assert_eq!(checker.variable("text"), Type::String);
+		assert!(runtime.check().is_empty());
+		runtime.strict = true;
+		let diagnostics = runtime.check();
+		assert_eq!(diagnostics.len(), 1);
+		assert_eq!(diagnostics[0].location, "implicit");
+		assert!(matches!(
+			diagnostics[0].problem,
+			TypeProblem::CannotRunOp(..)
+		));
+	}
+
+	#[test]
+	fn optional_types() {
+		let runtime = runtime(
+			r#"
+			struct Shield {
+				ac: int
+			}
+
+			struct Character {
+				name: string,
+				shield: Shield?
+			}
+
+			wizard = Character { name: "Tim" };
+			shield_ac = wizard.shield?.ac;
+			ac = 10 + (wizard.shield?.ac ?? 0);
+			found = [1, 2].index_of(2);
+			maybe = 1 > 2 ? 3 : none;
+			risky = wizard.shield.ac;
+			"#,
+		);
+		let checker = Checker::new(&runtime);
+		let optional_int = Type::Optional(Box::new(Type::Integer));
+		assert_eq!(checker.variable("shield_ac"), optional_int);
+		assert_eq!(checker.variable("ac"), Type::Integer);
+		assert_eq!(checker.variable("found"), optional_int);
+		assert_eq!(checker.variable("maybe"), optional_int);
+		let diagnostics = runtime.check();
+		assert_eq!(diagnostics.len(), 1);
+		assert_eq!(diagnostics[0].location, "risky");
+		assert!(diagnostics[0].is_warning());
+	}
+
+	#[test]
+	fn enum_types() {
+		let runtime = runtime(
+			r#"
+			enum Size { Small, Large }
+			enum Shape { Circle(decimal), Rect(int, int) }
+
+			struct Creature {
+				size: Size
+			}
+
+			goblin = Creature { size: Size.Small };
+			reach = (c: Creature) -> match c.size { Small => 5, Large => 10 };
+			area = match Shape.Rect(2, 3) { Circle(r) => r * r, Rect(w, h) => w * h };
+			partial = match Size.Large { Small => 1 };
+			typo = Size.Smal;
+			bad_payload = Shape.Rect("2", 3);
+			not_enum = match 3 { _ => 1 };
+			"#,
+		);
+		let checker = Checker::new(&runtime);
+		assert_eq!(
+			checker.variable("goblin"),
+			Type::Struct(StructName("Creature".to_string()))
+		);
+		assert_eq!(
+			checker.variable("reach"),
+			Type::Function(
+				vec![Type::Struct(StructName("Creature".to_string()))],
+				Box::new(Type::Integer)
+			)
+		);
+		assert_eq!(checker.variable("area"), Type::Decimal);
+		let problems = runtime
+			.check()
+			.into_iter()
+			.map(|d| (d.location, d.problem))
+			.collect::<Vec<_>>();
+		assert_eq!(problems.len(), 4);
+		assert!(problems.iter().any(|(l, p)| l == "partial"
+			&& matches!(p, TypeProblem::NonExhaustiveMatch(_, missing) if missing == &vec!["Large"])));
+		assert!(problems.iter().any(
+			|(l, p)| l == "typo" && matches!(p, TypeProblem::UnknownVariant(..))
+		));
+		assert!(problems.iter().any(|(l, p)| l == "bad_payload"
+			&& matches!(p, TypeProblem::WrongArgumentType(1, ..))));
+		assert!(problems.iter().any(
+			|(l, p)| l == "not_enum" && matches!(p, TypeProblem::CannotMatch(_))
+		));
+	}
+
+	#[test]
+	fn division_matches_runtime() {
+		let runtime = runtime(
+			r#"
+			odd = 15;
+			down = odd /_ 2;
+			up = odd /^ 2;
+			nearest = odd /~ 2;
+			below = (0 - odd) /_ 2;
+			half = odd / 2;
+			decimal_down = 7.5 /_ 2;
+			modifier = (score: int): int -> (score - 10) /_ 2;
+			mod = modifier(odd);
+			"#,
+		);
+		assert!(runtime.check().is_empty());
+		let checker = Checker::new(&runtime);
+		for name in &["down", "up", "nearest", "below", "mod"] {
+			assert_eq!(checker.variable(name), Type::Integer, "{}", name);
+			let value = runtime.run_val(name).unwrap();
+			assert!(matches!(value, RuntimeValue::Integer(_)), "{}", name);
+		}
+		for name in &["half", "decimal_down"] {
+			assert_eq!(checker.variable(name), Type::Decimal, "{}", name);
+			let value = runtime.run_val(name).unwrap();
+			assert!(matches!(value, RuntimeValue::Decimal(_)), "{}", name);
+		}
+	}
+
+	#[test]
+	fn function_call_syntax() {
+		let runtime = runtime(
+			r#"
+			modifier = (score: int) -> (score - 10) /_ 2;
+			add = (a: int, b: int) -> a + b;
+			score = 14;
+			mod = score.modifier();
+			sum = mod.add(2);
+			bad = "14".modifier();
+			missing = score.add();
+			extra = add(1, 2, 3);
+			uncalled = score.modifier;
+			"#,
+		);
+		let checker = Checker::new(&runtime);
+		assert_eq!(checker.variable("mod"), Type::Integer);
+		assert_eq!(checker.variable("sum"), Type::Integer);
+		let problems = runtime
+			.check()
+			.into_iter()
+			.map(|d| (d.location, d.problem))
+			.collect::<Vec<_>>();
+		assert_eq!(problems.len(), 4);
+		assert!(problems
+			.iter()
+			.any(|(l, p)| l == "bad"
+				&& matches!(p, TypeProblem::WrongArgumentType(1, ..))));
+		assert!(problems.iter().any(|(l, p)| l == "missing"
+			&& matches!(p, TypeProblem::WrongArgumentCount(2, 1))));
+		assert!(problems.iter().any(|(l, p)| l == "extra"
+			&& matches!(p, TypeProblem::WrongArgumentCount(2, 3))));
+		assert!(problems
+			.iter()
+			.any(|(l, p)| l == "uncalled" && matches!(p, TypeProblem::NotCalled(_))));
+	}
+}
@@ -0,0 +1,162 @@
+use std::fmt::Display;
+
+use ivory_expression::Expression;
+use ivory_tokenizer::{
+	ienum::{EnumDefinition, EnumVariant},
+	istruct::StructName,
+	values::match_value::{MatchArm, MatchPattern, MatchValue},
+};
+
+use crate::{
+	runtime::{Runtime, RuntimeContext},
+	value::Value,
+	Result, RuntimeError,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnumValue {
+	pub kind: StructName,
+	pub variant: String,
+	/// The values the variant holds, in the order they're defined in
+	pub values: Vec<Value>,
+}
+
+impl EnumValue {
+	/// Creates a variant of an enum, checking the values it holds against the
+	/// variant's definition.
+	pub fn build(
+		runtime: &Runtime,
+		def: &EnumDefinition,
+		variant: &EnumVariant,
+		values: Vec<Value>,
+	) -> Result<Self> {
+		if values.len() != variant.payload.len() {
+			return Err(RuntimeError::WrongPayload(
+				variant.name.0.clone(),
+				variant.payload.len(),
+				values.len(),
+			));
+		}
+		for (t, value) in variant.payload.iter().zip(values.iter()) {
+			if !value.is_type(t, &runtime.values) {
+				return Err(RuntimeError::WrongPayloadType(
+					variant.name.0.clone(),
+					t.clone(),
+					value.kind(),
+				));
+			}
+		}
+		Ok(Self {
+			kind: def.name.clone(),
+			variant: variant.name.0.clone(),
+			values,
+		})
+	}
+}
+
+impl Display for EnumValue {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		if self.values.is_empty() {
+			write!(f, "{}.{}", self.kind, self.variant)
+		} else {
+			write!(
+				f,
+				"{}.{}({})",
+				self.kind,
+				self.variant,
+				self
+					.values
+					.iter()
+					.map(|v| format!("{}", v))
+					.collect::<Vec<String>>()
+					.join(", ")
+			)
+		}
+	}
+}
+
+/// Checks that every pattern of a match is a variant of the enum, with the
+/// right number of names for the values it holds. Returns the variants that
+/// aren't handled, which is always empty if there's a wildcard.
+pub fn missing_variants(
+	def: &EnumDefinition,
+	arms: &[MatchArm],
+) -> Result<Vec<String>> {
+	let mut wildcard = false;
+	for arm in arms {
+		match &arm.pattern {
+			MatchPattern::Variant(name, bindings) => {
+				let variant = def.variant(&name.0).ok_or_else(|| {
+					RuntimeError::UnknownVariant(def.name.0.clone(), name.0.clone())
+				})?;
+				if bindings.len() != variant.payload.len() {
+					return Err(RuntimeError::WrongPayload(
+						name.0.clone(),
+						variant.payload.len(),
+						bindings.len(),
+					));
+				}
+			}
+			MatchPattern::Wildcard => wildcard = true,
+		}
+	}
+	if wildcard {
+		return Ok(Vec::new());
+	}
+	Ok(
+		def
+			.variants
+			.iter()
+			.filter(|v| {
+				!arms.iter().any(|arm| {
+					matches!(&arm.pattern, MatchPattern::Variant(name, _) if name == &v.name)
+				})
+			})
+			.map(|v| v.name.0.clone())
+			.collect(),
+	)
+}
+
+/// Evaluates the first arm of a match whose pattern fits the value, with the
+/// values the variant holds bound to the names in the pattern.
+pub fn run_match(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	m: &MatchValue,
+) -> Result<Value> {
+	let value = match runtime.evaluate(ctx, &m.value)? {
+		Value::Enum(e) => e,
+		other => return Err(RuntimeError::CannotMatchKind(other.kind())),
+	};
+	let def = runtime
+		.values
+		.enum_definition(&value.kind.0)
+		.ok_or_else(|| RuntimeError::StructNotFound(value.kind.0.clone()))?;
+	let missing = missing_variants(def, &m.arms)?;
+	if !missing.is_empty() {
+		return Err(RuntimeError::NonExhaustiveMatch(
+			def.name.0.clone(),
+			missing,
+		));
+	}
+
+	let mut arm_ctx = RuntimeContext::new();
+	for (name, param) in ctx.params.iter() {
+		arm_ctx.params.insert(name.clone(), param.clone());
+	}
+	for arm in &m.arms {
+		match &arm.pattern {
+			MatchPattern::Variant(name, bindings) if name.0 == value.variant => {
+				for (binding, v) in bindings.iter().zip(value.values.iter()) {
+					arm_ctx
+						.params
+						.insert(binding.0.clone(), Expression::new(v.clone()));
+				}
+			}
+			MatchPattern::Variant(..) => continue,
+			MatchPattern::Wildcard => {}
+		}
+		return runtime.evaluate(&arm_ctx, &arm.expr);
+	}
+	unreachable!("exhaustive matches always have an arm for every variant")
+}
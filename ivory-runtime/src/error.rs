@@ -1,5 +1,7 @@
 use quick_error::quick_error;
 
+use ivory_tokenizer::{expression::Op, itype::Type};
+
 use crate::{expr::RolledOp, value::ValueKind};
 
 pub type Result<T> = std::result::Result<T, RuntimeError>;
@@ -16,6 +18,92 @@ quick_error! {
 		RewriteNotImplemented {
 			display(s) -> ("This module loader cannot change source files")
 		}
+		CannotRewrite(path: String, err: String) {
+			display(s) -> ("Cannot change module \"{}\": {}", path, err)
+		}
+		StdModuleNotFound(path: String) {
+			display(s) -> ("There is no standard module called \"{}\"", path)
+		}
+	}
+}
+
+quick_error! {
+	/// Problems with a single field of a struct instance
+	#[derive(Debug, Clone)]
+	pub enum FieldError {
+		NotOnStruct(field: String) {
+			display(s) -> ("field {} is not on the struct", field)
+		}
+		Missing(field: String, expected: Type) {
+			display(s) -> ("missing field {} ({})", field, expected)
+		}
+		WrongType(field: String, expected: Type, got: ValueKind) {
+			display(s) -> ("field {} should be {}, got {}", field, expected, got)
+		}
+	}
+}
+
+quick_error! {
+	/// Problems found by checking the types of a module without running it
+	#[derive(Debug, Clone)]
+	pub enum TypeProblem {
+		UnknownVariable(name: String) {
+			display(s) -> ("Variable {} is not defined", name)
+		}
+		StructNotFound(name: String) {
+			display(s) -> ("Struct {} is not defined", name)
+		}
+		CannotRunOp(lhs: Type, op: Op, rhs: Type) {
+			display(s) -> ("Cannot perform operation {} {} {}", lhs, op, rhs)
+		}
+		Coercion(lhs: Type, op: Op, rhs: Type) {
+			display(s) -> ("{} {} {} implicitly converts between types", lhs, op, rhs)
+		}
+		MaybeNone(t: Type) {
+			display(s) -> ("{} may be none, check it with ?. or ?? first", t)
+		}
+		CannotIndex(t: Type) {
+			display(s) -> ("Cannot index into {}", t)
+		}
+		CannotCall(t: Type) {
+			display(s) -> ("Cannot call {}", t)
+		}
+		NotCalled(name: String) {
+			display(s) -> ("{} is a function, not a value, and must be called", name)
+		}
+		NoProperty(t: Type, prop: String) {
+			display(s) -> ("{} has no property {}", t, prop)
+		}
+		WrongArgumentCount(expected: usize, got: usize) {
+			display(s) -> ("Function takes {} arguments, got {}", expected, got)
+		}
+		WrongArgumentType(position: usize, expected: Type, got: Type) {
+			display(s) -> ("Argument {} should be {}, got {}", position, expected, got)
+		}
+		WrongReturnType(expected: Type, got: Type) {
+			display(s) -> ("Function should return {}, got {}", expected, got)
+		}
+		FieldNotOnStruct(struct_name: String, field: String) {
+			display(s) -> ("Field {} is not on {}", field, struct_name)
+		}
+		MissingField(struct_name: String, field: String, expected: Type) {
+			display(s) -> ("{} is missing field {} ({})", struct_name, field, expected)
+		}
+		WrongFieldType(field: String, expected: Type, got: Type) {
+			display(s) -> ("Field {} should be {}, got {}", field, expected, got)
+		}
+		UnknownVariant(enum_name: String, variant: String) {
+			display(s) -> ("{} has no variant {}", enum_name, variant)
+		}
+		WrongPayload(variant: String, expected: usize, got: usize) {
+			display(s) -> ("{} holds {} values, got {}", variant, expected, got)
+		}
+		NonExhaustiveMatch(enum_name: String, missing: Vec<String>) {
+			display(s) -> ("Match on {} doesn't handle {}", enum_name, missing.join(", "))
+		}
+		CannotMatch(t: Type) {
+			display(s) -> ("Cannot match on {}, only on enums", t)
+		}
 	}
 }
 
@@ -32,6 +120,15 @@ quick_error! {
 		WrongExpectedValue(expected: ValueKind, got: ValueKind) {
 			display(s) -> ("Expected value type {}, got {}", expected, got)
 		}
+		WrongArgumentType(param: String, expected: Type, got: ValueKind) {
+			display(s) -> ("Parameter {} should be {}, got {}", param, expected, got)
+		}
+		WrongArgumentCount(function: String, expected: usize, got: usize) {
+			display(s) -> ("{} takes {} arguments, got {}", function, expected, got)
+		}
+		WrongReturnType(expected: Type, got: ValueKind) {
+			display(s) -> ("Function should return {}, got {}", expected, got)
+		}
 		IncompatibleDiceOps {
 			display(s) -> ("Incompatible dice ops")
 		}
@@ -41,6 +138,9 @@ quick_error! {
 		VariableNotFound(var: String) {
 			display(s) -> ("Variable not found: {}", var)
 		}
+		VariableCycle(chain: Vec<String>) {
+			display(s) -> ("Variable depends on itself: {}", chain.join(" -> "))
+		}
 		NoPropertyOnKind(kind: ValueKind, prop: String) {
 			display(s) -> ("Can't get prop {} of kind {}", prop, kind)
 		}
@@ -59,8 +159,44 @@ quick_error! {
 		StructNotFound(struct_name: String) {
 			display(s) -> ("Struct type {} not found", struct_name)
 		}
-		FieldNotOnStruct(struct_name: String, field_name: String) {
-			display(s) -> ("Field {} not present on struct {}", field_name, struct_name)
+		InheritanceCycle(chain: Vec<String>) {
+			display(s) -> ("Struct extends itself: {}", chain.join(" -> "))
+		}
+		InvalidStruct(struct_name: String, errors: Vec<FieldError>) {
+			display(s) -> ("Invalid {}: {}", struct_name, errors.iter().map(|e| e.to_string()).collect::<Vec<String>>().join(", "))
+		}
+		UnknownVariant(enum_name: String, variant: String) {
+			display(s) -> ("{} has no variant {}", enum_name, variant)
+		}
+		WrongPayload(variant: String, expected: usize, got: usize) {
+			display(s) -> ("{} holds {} values, got {}", variant, expected, got)
+		}
+		WrongPayloadType(variant: String, expected: Type, got: ValueKind) {
+			display(s) -> ("{} should hold {}, got {}", variant, expected, got)
+		}
+		NonExhaustiveMatch(enum_name: String, missing: Vec<String>) {
+			display(s) -> ("Match on {} doesn't handle {}", enum_name, missing.join(", "))
+		}
+		CannotMatchKind(kind: ValueKind) {
+			display(s) -> ("Cannot match on kind {}, only on enums", kind)
+		}
+		TableRangeOutside(table: String, range: String, die: u32) {
+			display(s) -> ("Row {} of table {} isn't a roll from 1 to {}", range, table, die)
+		}
+		TableGap(table: String, start: u32, end: u32) {
+			display(s) -> ("Table {} has no row for rolls {} to {}", table, start, end)
+		}
+		TableOverlap(table: String, roll: u32) {
+			display(s) -> ("Table {} has more than one row for a roll of {}", table, roll)
+		}
+		CannotCast(from: ValueKind, to: ValueKind) {
+			display(s) -> ("Cannot cast {} to {}", from, to)
+		}
+		CannotParse(text: String, to: ValueKind) {
+			display(s) -> ("Cannot read \"{}\" as {}", text, to)
+		}
+		StrictCoercion(lhs: ValueKind, op: RolledOp, rhs: ValueKind) {
+			display(s) -> ("Cannot perform operation {} {} {} in strict mode, cast to a string first", lhs, op, rhs)
 		}
 		NoStdFnForKind(fn_name: String, kind: ValueKind) {
 			display(s) -> ("No standard function {} for kind \"{}\"", fn_name, kind)
@@ -68,6 +204,15 @@ quick_error! {
 		BadStdFnCall(info: String) {
 			display(s) -> ("{}", info)
 		}
+		NoSourceFile {
+			display(s) -> ("This runtime wasn't loaded from a file, so it can't be edited")
+		}
+		CannotEditVariable(var: String) {
+			display(s) -> ("Variable {} isn't defined as `{} = ...;`, so it can't be edited", var, var)
+		}
+		ModuleNotLoaded(path: String) {
+			display(s) -> ("No module has been loaded from \"{}\"", path)
+		}
 		ModLoader(err: ModLoaderError) {
 			from()
 			display(s) -> ("{}", err)
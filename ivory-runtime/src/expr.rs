@@ -1,10 +1,13 @@
 use std::fmt::Display;
 
-use crate::prec;
+use crate::prec::{self, Assoc};
 use ivory_expression::{Expression, ExpressionComponent, Pair};
 
 use ivory_tokenizer::expression::logic::{Comparator, LogicOp};
-use ivory_tokenizer::expression::math::{ExprOpMathKind, ExprOpMathRound};
+use ivory_tokenizer::expression::math::{
+	ExprOpMath, ExprOpMathKind, ExprOpMathRound,
+};
+use ivory_tokenizer::expression::Op;
 
 use crate::runtime::{Runtime, RuntimeContext};
 use crate::value::Value;
@@ -19,6 +22,7 @@ pub enum RolledOp {
 	Ternary(Box<Expression<RolledOp, Value>>),
 	Comparator(Comparator),
 	Logic(LogicOp),
+	Coalesce,
 }
 
 impl Display for RolledOp {
@@ -32,6 +36,64 @@ impl Display for RolledOp {
 
 			RolledOp::Comparator(c) => write!(f, "{}", c),
 			RolledOp::Logic(l) => write!(f, "{}", l),
+			RolledOp::Coalesce => write!(f, "??"),
+		}
+	}
+}
+
+/// How tightly each kind of operator binds, from the loosest to the tightest.
+/// The runtime and the type checker both group expressions with this.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Precedence {
+	Ternary,
+	Coalesce,
+	Logic,
+	Comparator,
+	Sum,
+	Product,
+	/// Dice and dice operators are handled before precedence matters
+	Dice,
+}
+
+impl Precedence {
+	/// The precedence and associativity used to climb an expression
+	pub fn rule(self) -> (usize, Assoc) {
+		let assoc = match self {
+			Precedence::Ternary | Precedence::Product => Assoc::Right,
+			_ => Assoc::Left,
+		};
+		(self as usize, assoc)
+	}
+
+	fn math(kind: &ExprOpMathKind) -> Self {
+		match kind {
+			ExprOpMathKind::Add | ExprOpMathKind::Sub => Precedence::Sum,
+			ExprOpMathKind::Mul | ExprOpMathKind::Div => Precedence::Product,
+		}
+	}
+}
+
+impl From<&Op> for Precedence {
+	fn from(op: &Op) -> Self {
+		match op {
+			Op::Math(ExprOpMath::Ternary(_)) => Precedence::Ternary,
+			Op::Math(ExprOpMath::Binary { kind, .. }) => Precedence::math(kind),
+			Op::Coalesce => Precedence::Coalesce,
+			Op::Logic(_) => Precedence::Logic,
+			Op::Comparator(_) => Precedence::Comparator,
+			Op::Dice | Op::DiceOp(_) => Precedence::Dice,
+		}
+	}
+}
+
+impl From<&RolledOp> for Precedence {
+	fn from(op: &RolledOp) -> Self {
+		match op {
+			RolledOp::Ternary(_) => Precedence::Ternary,
+			RolledOp::Math { kind, .. } => Precedence::math(kind),
+			RolledOp::Coalesce => Precedence::Coalesce,
+			RolledOp::Logic(_) => Precedence::Logic,
+			RolledOp::Comparator(_) => Precedence::Comparator,
 		}
 	}
 }
@@ -0,0 +1,726 @@
+use std::{
+	collections::{hash_map::Entry, BTreeMap, BTreeSet, HashMap, HashSet},
+	fmt::Display,
+};
+
+use ivory_expression::{Expression, ExpressionComponent, Pair};
+use ivory_tokenizer::{
+	accessor::{Accessor, AccessorComponent, AccessorRoot},
+	expression::{math::ExprOpMath, ExpressionToken, Op},
+	istruct::StructDefinitionValue,
+	values::{function::FunctionValue, match_value::MatchPattern, Value},
+	variable::Variable,
+};
+
+use crate::{
+	runtime::RuntimeValues, std_fns::StdFnLibrary, table, Result, RuntimeError,
+};
+
+/// Where a reference appears inside of a variable's definition.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RefScope {
+	/// Evaluated every time the variable is.
+	Value,
+	/// Inside the body of a variable that is defined as a function, only
+	/// evaluated when that function is called.
+	Body,
+}
+
+/// A reference from one variable to another.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reference {
+	pub name: String,
+	/// The reference is called right away, like `foo(1)`
+	pub called: bool,
+	pub scope: RefScope,
+}
+
+/// Everything a single variable definition refers to.
+#[derive(Clone, Debug)]
+pub struct Node {
+	pub refs: Vec<Reference>,
+	/// The variable is defined as a function literal, like `foo = a -> a + 1`
+	pub function: bool,
+	/// The definition contains a dice roll somewhere in it, or calls a function
+	/// that isn't pure
+	pub rolls_dice: bool,
+	/// Neither the definition nor anything it depends on rolls dice, so it
+	/// always evaluates to the same value.
+	pub pure: bool,
+}
+
+impl Node {
+	/// `values` are the definitions visible to the variable, so the struct
+	/// defaults and methods it uses are part of its definition.
+	pub fn new(
+		variable: &Variable,
+		values: &RuntimeValues,
+		std_fns: &StdFnLibrary,
+	) -> Self {
+		let mut walker = Walker {
+			refs: Vec::new(),
+			rolls_dice: false,
+			values,
+			std_fns,
+			methods: HashSet::new(),
+		};
+		let function = match as_function(&variable.value) {
+			Some(FunctionValue { args, expr, .. }) => {
+				let bound = args.iter().map(|a| a.0.clone()).collect();
+				walker.expr(expr, RefScope::Body, &bound);
+				true
+			}
+			None => {
+				walker.expr(&variable.value, RefScope::Value, &HashSet::new());
+				false
+			}
+		};
+		Self {
+			refs: walker.refs,
+			function,
+			rolls_dice: walker.rolls_dice,
+			pure: !walker.rolls_dice,
+		}
+	}
+}
+
+/// The references between every variable visible from the root of a runtime.
+///
+/// Names are resolved the same way the runtime resolves them, so variables
+/// imported from other modules (including aliased ones) are part of the graph,
+/// along with the variables they use that their module didn't export.
+#[derive(Clone, Debug, Default)]
+pub struct DependencyGraph {
+	pub nodes: BTreeMap<String, Node>,
+}
+
+impl DependencyGraph {
+	pub fn new(values: &RuntimeValues, std_fns: &StdFnLibrary) -> Self {
+		let mut nodes = BTreeMap::new();
+		let mut pending = values.variable_names().into_iter().collect::<Vec<_>>();
+		while let Some(name) = pending.pop() {
+			if nodes.contains_key(&name) {
+				continue;
+			}
+			if let Some(variable) = values.get_variable(&name) {
+				let node = Node::new(variable, values, std_fns);
+				// the variables a module didn't export are only found through the
+				// ones it did
+				pending.extend(node.refs.iter().map(|r| r.name.clone()));
+				nodes.insert(name, node);
+			}
+		}
+		// references to names that aren't variables (like misplaced function
+		// parameters) aren't part of the graph.
+		let names = nodes.keys().cloned().collect::<HashSet<String>>();
+		for node in nodes.values_mut() {
+			node.refs.retain(|r| names.contains(&r.name));
+		}
+		let mut graph = Self { nodes };
+		graph.mark_impure();
+		graph
+	}
+
+	/// Spreads impurity from every variable that rolls dice to everything that
+	/// depends on it.
+	fn mark_impure(&mut self) {
+		let mut changed = true;
+		while changed {
+			changed = false;
+			let impure = self
+				.nodes
+				.iter()
+				.filter(|(_, node)| !node.pure)
+				.map(|(name, _)| name.clone())
+				.collect::<HashSet<String>>();
+			for node in self.nodes.values_mut() {
+				if node.pure && node.refs.iter().any(|r| impure.contains(&r.name)) {
+					node.pure = false;
+					changed = true;
+				}
+			}
+		}
+	}
+
+	pub fn is_pure(&self, name: &str) -> bool {
+		matches!(self.nodes.get(name), Some(node) if node.pure)
+	}
+
+	/// The variables directly referred to in a variable's definition.
+	pub fn dependencies_of(&self, name: &str) -> BTreeSet<&str> {
+		match self.nodes.get(name) {
+			Some(node) => node.refs.iter().map(|r| r.name.as_str()).collect(),
+			None => BTreeSet::new(),
+		}
+	}
+
+	/// The variables whose definitions directly refer to a variable.
+	pub fn dependents_of(&self, name: &str) -> BTreeSet<&str> {
+		self
+			.nodes
+			.iter()
+			.filter(|(_, node)| node.refs.iter().any(|r| r.name == name))
+			.map(|(n, _)| n.as_str())
+			.collect()
+	}
+
+	/// Everything a variable depends on, transitively.
+	pub fn dependency_tree(&self, name: &str) -> DependencyTree {
+		DependencyTree::build(name, &mut HashSet::new(), &|n| {
+			self.dependencies_of(n)
+		})
+	}
+
+	/// Everything that depends on a variable, transitively.
+	pub fn dependent_tree(&self, name: &str) -> DependencyTree {
+		DependencyTree::build(name, &mut HashSet::new(), &|n| self.dependents_of(n))
+	}
+
+	/// The names in `dependent_tree`, found without building the tree.
+	pub fn all_dependents(&self, name: &str) -> BTreeSet<&str> {
+		let mut found = BTreeSet::new();
+		let mut stack = vec![name];
+		while let Some(name) = stack.pop() {
+			for dependent in self.dependents_of(name) {
+				if found.insert(dependent) {
+					stack.push(dependent);
+				}
+			}
+		}
+		found
+	}
+
+	/// Returns an error with the full chain of names if any variable depends on
+	/// its own value.
+	///
+	/// Recursive functions are allowed, since they only refer to themselves
+	/// when they're called.
+	pub fn check_cycles(&self) -> Result<()> {
+		for name in self.nodes.keys() {
+			if let Some(chain) = self.find_cycle(name) {
+				return Err(RuntimeError::VariableCycle(chain));
+			}
+		}
+		Ok(())
+	}
+
+	/// Searches for a path from evaluating a variable back to evaluating the
+	/// same variable.
+	///
+	/// Evaluating a variable and calling a variable are separate steps in the
+	/// search, which is what tells value cycles apart from recursion: a cycle
+	/// that's only made up of calls never needs a variable's value.
+	fn find_cycle(&self, name: &str) -> Option<Vec<String>> {
+		let start: Step = (name, false);
+		let mut parents = HashMap::<Step, Step>::new();
+		let mut stack = vec![start];
+		while let Some(step) = stack.pop() {
+			for next in self.steps(step) {
+				if next == start {
+					let mut chain = vec![next];
+					let mut current = step;
+					while current != start {
+						chain.push(current);
+						current = parents[&current];
+					}
+					chain.push(start);
+					chain.reverse();
+					return Some(chain.into_iter().map(|(n, _)| n.to_string()).collect());
+				}
+				if let Entry::Vacant(entry) = parents.entry(next) {
+					entry.insert(step);
+					stack.push(next);
+				}
+			}
+		}
+		None
+	}
+
+	/// Everything that is evaluated or called as part of evaluating or calling
+	/// a variable.
+	fn steps<'a>(&'a self, (name, call): Step<'a>) -> Vec<Step<'a>> {
+		let node = match self.nodes.get(name) {
+			Some(node) => node,
+			None => return Vec::new(),
+		};
+		let scope = if call && node.function {
+			RefScope::Body
+		} else {
+			RefScope::Value
+		};
+		let mut steps = Vec::new();
+		for r in node.refs.iter().filter(|r| r.scope == scope) {
+			// calling something that isn't a function literal calls whatever it
+			// evaluates to.
+			if !call || node.function {
+				steps.push((r.name.as_str(), false));
+			}
+			if r.called || (call && !node.function) {
+				steps.push((r.name.as_str(), true));
+			}
+		}
+		steps
+	}
+}
+
+/// A variable, and the variables it depends on (or that depend on it).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DependencyTree {
+	pub name: String,
+	pub children: Vec<DependencyTree>,
+}
+
+impl DependencyTree {
+	fn build<'a, F>(name: &'a str, seen: &mut HashSet<&'a str>, next: &F) -> Self
+	where
+		F: Fn(&str) -> BTreeSet<&'a str>,
+	{
+		// names that are already in the tree, like recursive functions or
+		// variables that many others share, show up again without their children
+		if !seen.insert(name) {
+			return Self {
+				name: name.to_string(),
+				children: Vec::new(),
+			};
+		}
+		let children = next(name)
+			.into_iter()
+			.map(|child| Self::build(child, seen, next))
+			.collect();
+		Self {
+			name: name.to_string(),
+			children,
+		}
+	}
+
+	/// Every name in the tree, not including the root.
+	pub fn names(&self) -> BTreeSet<&str> {
+		let mut names = BTreeSet::new();
+		for child in &self.children {
+			names.insert(child.name.as_str());
+			names.extend(child.names());
+		}
+		names
+	}
+
+	fn fmt_children(
+		&self,
+		f: &mut std::fmt::Formatter<'_>,
+		prefix: &str,
+	) -> std::fmt::Result {
+		for (i, child) in self.children.iter().enumerate() {
+			let last = i == self.children.len() - 1;
+			write!(
+				f,
+				"\n{}{}{}",
+				prefix,
+				if last { "└── " } else { "├── " },
+				child.name
+			)?;
+			child.fmt_children(
+				f,
+				&format!("{}{}", prefix, if last { "    " } else { "│   " }),
+			)?;
+		}
+		Ok(())
+	}
+}
+
+impl Display for DependencyTree {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self.name)?;
+		self.fmt_children(f, "")
+	}
+}
+
+/// A variable's name, and whether it's being called (true) or evaluated (false)
+type Step<'a> = (&'a str, bool);
+
+/// The function a variable is defined as, if it's defined as a function literal
+pub(crate) fn as_function(
+	expr: &Expression<Op, ExpressionToken>,
+) -> Option<&FunctionValue> {
+	match &expr.first {
+		ExpressionComponent::Token(ExpressionToken(Accessor(
+			AccessorRoot::Value(Value::Function(f)),
+			components,
+		)))
+			if components.is_empty() && expr.pairs.is_empty() =>
+		{
+			Some(f)
+		}
+		_ => None,
+	}
+}
+
+struct Walker<'a> {
+	refs: Vec<Reference>,
+	rolls_dice: bool,
+	values: &'a RuntimeValues,
+	std_fns: &'a StdFnLibrary,
+	/// Methods that have already been walked, since they can call themselves
+	methods: HashSet<String>,
+}
+
+impl<'a> Walker<'a> {
+	fn expr(
+		&mut self,
+		expr: &Expression<Op, ExpressionToken>,
+		scope: RefScope,
+		bound: &HashSet<String>,
+	) {
+		self.component(&expr.first, scope, bound);
+		for Pair(op, component) in &expr.pairs {
+			match op {
+				Op::Dice => self.rolls_dice = true,
+				Op::Math(ExprOpMath::Ternary(ternary)) => {
+					self.expr(ternary, scope, bound)
+				}
+				_ => {}
+			}
+			self.component(component, scope, bound);
+		}
+	}
+
+	fn component(
+		&mut self,
+		component: &ExpressionComponent<Op, ExpressionToken>,
+		scope: RefScope,
+		bound: &HashSet<String>,
+	) {
+		match component {
+			ExpressionComponent::Token(ExpressionToken(accessor)) => {
+				self.accessor(accessor, scope, bound)
+			}
+			ExpressionComponent::Paren(paren) => self.expr(paren, scope, bound),
+		}
+	}
+
+	fn accessor(
+		&mut self,
+		Accessor(root, components): &Accessor,
+		scope: RefScope,
+		bound: &HashSet<String>,
+	) {
+		match root {
+			AccessorRoot::Variable(name) => {
+				let called =
+					matches!(components.first(), Some(AccessorComponent::Call(_)));
+				let shadowed = bound.contains(&name.0)
+					|| self.values.get_variable(&name.0).is_some();
+				if called && !shadowed && !self.std_fns.is_pure(&name.0) {
+					self.rolls_dice = true;
+				}
+				if !bound.contains(&name.0)
+					&& table::is_roll(components)
+					&& self.values.table(&name.0).is_some()
+				{
+					self.rolls_dice = true;
+				}
+				if !bound.contains(&name.0) {
+					self.refs.push(Reference {
+						name: name.0.clone(),
+						called,
+						scope,
+					});
+				}
+			}
+			AccessorRoot::Value(value) => self.value(value, scope, bound),
+		}
+		for (i, component) in components.iter().enumerate() {
+			match component {
+				AccessorComponent::Property(prop)
+				| AccessorComponent::OptionalProperty(prop) => {
+					// The type of a value isn't known until it's evaluated, so a call
+					// to any method with this name might happen here
+					if let Some(AccessorComponent::Call(_)) = components.get(i + 1) {
+						if !self.std_fns.is_pure(&prop.0) {
+							self.rolls_dice = true;
+						}
+						self.method_calls(&prop.0, scope, bound);
+						// functions defined in modules can be called on values too
+						if self.values.function(&prop.0).is_some() {
+							self.refs.push(Reference {
+								name: prop.0.clone(),
+								called: true,
+								scope,
+							});
+						}
+					}
+				}
+				AccessorComponent::Index(i) => self.expr(i, scope, bound),
+				AccessorComponent::Call(args) => {
+					for arg in args {
+						self.expr(arg, scope, bound);
+					}
+				}
+			}
+		}
+	}
+
+	fn method_calls(
+		&mut self,
+		name: &str,
+		scope: RefScope,
+		bound: &HashSet<String>,
+	) {
+		let values = self.values;
+		for (struct_name, methods) in &values.methods {
+			if let Some(method) = methods.get(name) {
+				if !self.methods.insert(format!("{}.{}", struct_name, name)) {
+					continue;
+				}
+				let mut bound = bound.clone();
+				bound.insert("self".to_string());
+				match as_function(&method.value) {
+					Some(FunctionValue { args, expr, .. }) => {
+						bound.extend(args.iter().map(|a| a.0.clone()));
+						self.expr(expr, scope, &bound);
+					}
+					None => self.expr(&method.value, scope, &bound),
+				}
+			}
+		}
+	}
+
+	fn value(&mut self, value: &Value, scope: RefScope, bound: &HashSet<String>) {
+		match value {
+			Value::Array(array) => {
+				for v in &array.0 {
+					self.expr(v, scope, bound);
+				}
+			}
+			Value::Object(object) => {
+				for v in object.0.values() {
+					self.expr(v, scope, bound);
+				}
+			}
+			Value::Struct(instance) => {
+				for v in instance.values.values() {
+					self.expr(v, scope, bound);
+				}
+				// defaults that aren't overridden are evaluated with every field
+				// of the struct in scope
+				if let Ok(fields) = self.values.struct_fields(&instance.name.0) {
+					let mut bound = bound.clone();
+					bound.extend(fields.iter().map(|(name, _)| name.0.clone()));
+					for (name, def_value) in &fields {
+						if let StructDefinitionValue::Value(v) = def_value {
+							if !instance.values.contains_key(name) {
+								self.expr(v, scope, &bound);
+							}
+						}
+					}
+				}
+			}
+			Value::Match(m) => {
+				self.expr(&m.value, scope, bound);
+				for arm in &m.arms {
+					let mut bound = bound.clone();
+					if let MatchPattern::Variant(_, bindings) = &arm.pattern {
+						bound.extend(bindings.iter().map(|b| b.0.clone()));
+					}
+					self.expr(&arm.expr, scope, &bound);
+				}
+			}
+			Value::Function(FunctionValue { args, expr, .. }) => {
+				let mut bound = bound.clone();
+				bound.extend(args.iter().map(|a| a.0.clone()));
+				// a nested function literal can be called by whatever it's passed
+				// to, so its body counts as part of the surrounding scope
+				self.expr(expr, scope, &bound);
+			}
+			Value::Boolean(_)
+			| Value::Decimal(_)
+			| Value::Integer(_)
+			| Value::String(_)
+			| Value::None(_) => {}
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::DependencyTree;
+	use crate::runtime::Runtime;
+
+	fn load(src: &str) -> crate::Result<()> {
+		Runtime::new(rand::thread_rng(), ()).load(src, "")
+	}
+
+	fn cycle(src: &str) -> Vec<String> {
+		match load(src) {
+			Err(crate::RuntimeError::VariableCycle(chain)) => chain,
+			other => panic!("Expected a variable cycle, got {:?}", other.err()),
+		}
+	}
+
+	#[test]
+	fn value_cycles() {
+		assert_eq!(cycle("a = a + 1;"), vec!["a", "a"]);
+		assert_eq!(
+			cycle("ac = base + dex_bonus; base = 10; dex_bonus = ac - 10;"),
+			vec!["ac", "dex_bonus", "ac"]
+		);
+		assert_eq!(
+			cycle("a = [1, 2, b[0]]; b = { c: c }; c = 3 > 2 ? a : 1;"),
+			vec!["a", "b", "c", "a"]
+		);
+	}
+
+	#[test]
+	fn cycles_through_calls() {
+		assert_eq!(cycle("a = f(1); f = x -> a + x;"), vec!["a", "f", "a"]);
+		assert_eq!(
+			cycle("a = g(1); g = f; f = x -> a + x;"),
+			vec!["a", "g", "f", "a"]
+		);
+	}
+
+	#[test]
+	fn cycles_through_lambdas() {
+		assert_eq!(cycle("a = [1, 2].map(x -> a);"), vec!["a", "a"]);
+		assert_eq!(
+			cycle("a = b([1]); b = xs -> xs.map(x -> a);"),
+			vec!["a", "b", "a"]
+		);
+		load("f = n -> [n].map(x -> x > 0 ? f(x - 1) : 0);").unwrap();
+	}
+
+	#[test]
+	fn recursion_is_not_a_cycle() {
+		load("fact = n -> n <= 1 ? 1 : n * fact(n - 1);").unwrap();
+		load("even = n -> n == 0 ? true : odd(n - 1); odd = n -> even(n - 1);")
+			.unwrap();
+		load("f = x -> x + y; y = 10; z = f(y);").unwrap();
+		// function parameters shadow variables of the same name
+		load("x = f(1); f = x -> x + 1;").unwrap();
+	}
+
+	#[test]
+	fn struct_defaults() {
+		let mut runtime = Runtime::new(rand::thread_rng(), ());
+		runtime
+			.load(
+				r#"
+		struct Stat { score: int, mod: score + base }
+		struct Attack { bonus: 1d20 }
+		base = 2;
+		str = Stat { score: 10 };
+		dex = Stat { score: 12, mod: 1 };
+		attack = Attack {};
+		"#,
+				"",
+			)
+			.unwrap();
+		let graph = &runtime.graph;
+		assert_eq!(
+			graph
+				.dependencies_of("str")
+				.into_iter()
+				.collect::<Vec<&str>>(),
+			vec!["base"]
+		);
+		assert!(graph.dependencies_of("dex").is_empty());
+		assert!(graph.is_pure("str"));
+		assert!(!graph.is_pure("attack"));
+	}
+
+	fn sheet() -> Runtime {
+		let mut runtime = Runtime::new(rand::thread_rng(), ());
+		runtime
+			.load(
+				r#"
+			str = 15 + 1;
+			dex = 15;
+			ability_mod = ability -> (ability - 10) /_ 2;
+			str_mod = ability_mod(str);
+			ability_check = ability -> 1d20 + ability_mod(ability);
+			str_check = ability_check(str);
+			dex_check = ability_check(dex);
+			"#,
+				"",
+			)
+			.unwrap();
+		runtime
+	}
+
+	fn leaf(name: &str) -> DependencyTree {
+		DependencyTree {
+			name: name.to_string(),
+			children: Vec::new(),
+		}
+	}
+
+	#[test]
+	fn dependencies() {
+		let runtime = sheet();
+		let tree = runtime.dependencies("str_check").unwrap();
+		assert_eq!(
+			tree,
+			DependencyTree {
+				name: "str_check".to_string(),
+				children: vec![
+					DependencyTree {
+						name: "ability_check".to_string(),
+						children: vec![leaf("ability_mod")],
+					},
+					leaf("str"),
+				],
+			}
+		);
+		assert_eq!(
+			format!("{}", tree),
+			"str_check\n├── ability_check\n│   └── ability_mod\n└── str"
+		);
+		assert!(runtime.dependencies("not_a_variable").is_err());
+	}
+
+	#[test]
+	fn dependents() {
+		let runtime = sheet();
+		let tree = runtime.dependents("ability_mod").unwrap();
+		assert_eq!(
+			tree.names().into_iter().collect::<Vec<&str>>(),
+			vec!["ability_check", "dex_check", "str_check", "str_mod"]
+		);
+		assert_eq!(
+			runtime.dependents("str").unwrap().names(),
+			["str_check", "str_mod"].iter().cloned().collect()
+		);
+	}
+
+	#[test]
+	fn shared_dependents() {
+		// every variable adds up the two before it, so each one is reachable
+		// from v0 by many paths
+		let src: String = (2..28)
+			.map(|i| format!("v{} = v{} + v{};", i, i - 1, i - 2))
+			.collect();
+		let mut runtime = Runtime::new(rand::thread_rng(), ());
+		runtime
+			.load(&format!("v0 = 1; v1 = 1; {}", src), "")
+			.unwrap();
+		let tree = runtime.dependents("v0").unwrap();
+		assert_eq!(tree.names().len(), 26);
+		// a dependent that was already expanded is listed again without children
+		assert_eq!(format!("{}", tree).lines().count(), 51);
+		assert_eq!(runtime.graph.all_dependents("v0"), tree.names());
+		assert_eq!(runtime.dependencies("v27").unwrap().names().len(), 27);
+	}
+
+	#[test]
+	fn recursive_dependencies() {
+		let mut runtime = Runtime::new(rand::thread_rng(), ());
+		runtime
+			.load("fact = n -> n <= 1 ? 1 : n * fact(n - 1);", "")
+			.unwrap();
+		assert_eq!(
+			runtime.dependencies("fact").unwrap(),
+			DependencyTree {
+				name: "fact".to_string(),
+				children: vec![leaf("fact")],
+			}
+		);
+	}
+}
@@ -1,11 +1,19 @@
+// quick_error needs this for an enum with as many variants as RuntimeError
+#![recursion_limit = "256"]
+
+pub mod check;
+pub mod enum_value;
 pub mod error;
 pub mod expr;
+pub mod graph;
+pub mod math;
 pub mod mod_loader;
 pub mod prec;
 pub mod roll;
 pub mod runtime;
 pub mod std_fns;
 pub mod struct_value;
+pub mod table;
 pub mod value;
 
 pub use crate::error::{Result, RuntimeError};
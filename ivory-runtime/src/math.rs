@@ -0,0 +1,344 @@
+use std::{
+	collections::HashMap,
+	convert::{TryFrom, TryInto},
+};
+
+use ivory_tokenizer::expression::math::ExprOpMathRound;
+
+use crate::{value::Value, Result, RuntimeError};
+
+type MathFn = fn(args: &[Value]) -> Result<Value>;
+
+/// The `math` namespace, with functions called like `math.sqrt(x)` and
+/// constants like `math.pi`. Unlike standard functions, these aren't called
+/// on a value.
+pub struct MathLibrary {
+	pub fns: HashMap<String, MathFn>,
+	pub constants: HashMap<String, Value>,
+}
+
+impl MathLibrary {
+	pub fn new() -> Self {
+		let mut fns = HashMap::<String, MathFn>::new();
+		fns.insert("abs".to_string(), abs);
+		fns.insert("sqrt".to_string(), sqrt);
+		fns.insert("pow".to_string(), pow);
+		fns.insert("floor".to_string(), floor);
+		fns.insert("ceil".to_string(), ceil);
+		fns.insert("round".to_string(), round);
+		fns.insert("min".to_string(), min);
+		fns.insert("max".to_string(), max);
+		fns.insert("clamp".to_string(), clamp);
+		fns.insert("sign".to_string(), sign);
+		fns.insert("log".to_string(), log);
+
+		let mut constants = HashMap::new();
+		constants.insert("pi".to_string(), Value::Decimal(std::f32::consts::PI));
+		constants.insert("tau".to_string(), Value::Decimal(std::f32::consts::TAU));
+		constants.insert("e".to_string(), Value::Decimal(std::f32::consts::E));
+
+		Self { fns, constants }
+	}
+
+	pub fn call(&self, name: &str, args: &[Value]) -> Result<Value> {
+		match self.fns.get(name) {
+			Some(f) => f(args).map_err(|err| match err {
+				RuntimeError::BadStdFnCall(info) => RuntimeError::BadStdFnCall(
+					format!("Error calling math.{}: {}", name, info),
+				),
+				other => other,
+			}),
+			None => Err(RuntimeError::PropNotFound(format!("math.{}", name))),
+		}
+	}
+
+	pub fn has(&self, name: &str) -> bool {
+		self.fns.contains_key(name)
+	}
+}
+
+impl Default for MathLibrary {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+fn exactly<const N: usize>(args: &[Value]) -> Result<&[Value; N]> {
+	args.try_into().map_err(|_| {
+		RuntimeError::BadStdFnCall(format!("function requires {} arguments", N))
+	})
+}
+
+fn at_least_one(args: &[Value]) -> Result<()> {
+	if args.is_empty() {
+		Err(RuntimeError::BadStdFnCall(
+			"function requires at least 1 argument".to_string(),
+		))
+	} else {
+		Ok(())
+	}
+}
+
+/// Decimals stay decimals, everything else is used like an integer, the same
+/// as it would be for math operators
+fn is_decimal(v: &Value) -> Result<bool> {
+	match v {
+		Value::Decimal(_) => Ok(true),
+		_ => v.to_integer().map(|_| false),
+	}
+}
+
+/// Rounds the same way as `/^`, `/_` and `/~`, giving an integer
+fn round_with(v: &Value, round: ExprOpMathRound) -> Result<Value> {
+	if !is_decimal(v)? {
+		return Ok(Value::Integer(v.to_integer()?));
+	}
+	let d = v.to_decimal()?;
+	Ok(Value::Integer(match round {
+		ExprOpMathRound::Up => d.ceil(),
+		ExprOpMathRound::Down => d.floor(),
+		ExprOpMathRound::Round => d.round(),
+	} as i32))
+}
+
+/// The smallest or biggest of the arguments, keeping its type. Every argument
+/// has to be a number, even when there's only one.
+fn extreme(args: &[Value], bigger: bool) -> Result<Value> {
+	at_least_one(args)?;
+	let mut best = (&args[0], args[0].to_decimal()?);
+	for v in &args[1..] {
+		let d = v.to_decimal()?;
+		if (bigger && d > best.1) || (!bigger && d < best.1) {
+			best = (v, d);
+		}
+	}
+	Ok(best.0.clone())
+}
+
+// ========================================================================== //
+
+pub fn abs(args: &[Value]) -> Result<Value> {
+	let [x] = exactly(args)?;
+	if is_decimal(x)? {
+		Ok(Value::Decimal(x.to_decimal()?.abs()))
+	} else {
+		let i = x.to_integer()?;
+		i.checked_abs().map(Value::Integer).ok_or_else(|| {
+			RuntimeError::BadStdFnCall(format!(
+				"the absolute value of {} is too big for an integer",
+				i
+			))
+		})
+	}
+}
+
+pub fn sqrt(args: &[Value]) -> Result<Value> {
+	let [x] = exactly(args)?;
+	Ok(Value::Decimal(x.to_decimal()?.sqrt()))
+}
+
+/// Integers raised to positive integer powers stay integers
+pub fn pow(args: &[Value]) -> Result<Value> {
+	let [base, exp] = exactly(args)?;
+	if !is_decimal(base)? && !is_decimal(exp)? {
+		if let Ok(exp) = u32::try_from(exp.to_integer()?) {
+			if let Some(res) = base.to_integer()?.checked_pow(exp) {
+				return Ok(Value::Integer(res));
+			}
+		}
+	}
+	Ok(Value::Decimal(base.to_decimal()?.powf(exp.to_decimal()?)))
+}
+
+pub fn floor(args: &[Value]) -> Result<Value> {
+	let [x] = exactly(args)?;
+	round_with(x, ExprOpMathRound::Down)
+}
+
+pub fn ceil(args: &[Value]) -> Result<Value> {
+	let [x] = exactly(args)?;
+	round_with(x, ExprOpMathRound::Up)
+}
+
+/// Halves are rounded away from zero
+pub fn round(args: &[Value]) -> Result<Value> {
+	let [x] = exactly(args)?;
+	round_with(x, ExprOpMathRound::Round)
+}
+
+pub fn min(args: &[Value]) -> Result<Value> {
+	extreme(args, false)
+}
+
+pub fn max(args: &[Value]) -> Result<Value> {
+	extreme(args, true)
+}
+
+/// `math.clamp(x, low, high)` keeps x between low and high
+pub fn clamp(args: &[Value]) -> Result<Value> {
+	let [x, low, high] = exactly(args)?;
+	let lowest = extreme(&[x.clone(), low.clone()], true)?;
+	extreme(&[lowest, high.clone()], false)
+}
+
+/// -1, 0 or 1
+pub fn sign(args: &[Value]) -> Result<Value> {
+	let [x] = exactly(args)?;
+	let x = x.to_decimal()?;
+	Ok(Value::Integer(if x > 0.0 {
+		1
+	} else if x < 0.0 {
+		-1
+	} else {
+		0
+	}))
+}
+
+/// The natural logarithm, or the logarithm with a base given as the second
+/// argument
+pub fn log(args: &[Value]) -> Result<Value> {
+	match args {
+		[x] => Ok(Value::Decimal(x.to_decimal()?.ln())),
+		[x, base] => Ok(Value::Decimal(x.to_decimal()?.log(base.to_decimal()?))),
+		_ => Err(RuntimeError::BadStdFnCall(
+			"function requires 1 or 2 arguments".to_string(),
+		)),
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::{
+		check::Checker,
+		runtime::{assert_cases, Runtime},
+		value::Value,
+		RuntimeError,
+	};
+	use ivory_tokenizer::itype::Type;
+
+	fn test_runtime() -> Runtime {
+		let mut runtime = Runtime::new(rand::thread_rng(), ());
+		runtime
+			.load(
+				r#"
+			hypot = a b -> math.sqrt(a * a + b * b);
+			modifier = score -> math.floor((score - 10) / 2);
+			area = r -> math.pi * r * r;
+			"#,
+				"",
+			)
+			.unwrap();
+		runtime
+	}
+
+	#[test]
+	fn math_fns() {
+		let runtime = test_runtime();
+		assert_cases(
+			&runtime,
+			&[
+				("math.abs(-3)", Value::Integer(3)),
+				("math.abs(0 - 2.5)", Value::Decimal(2.5)),
+				("math.sqrt(16)", Value::Decimal(4.0)),
+				("hypot(3, 4)", Value::Decimal(5.0)),
+				("math.pow(2, 10)", Value::Integer(1024)),
+				("math.pow(4, 0.5)", Value::Decimal(2.0)),
+				("math.pow(2, -1)", Value::Decimal(0.5)),
+				("math.floor(2.7)", Value::Integer(2)),
+				("math.floor(0 - 2.5)", Value::Integer(-3)),
+				("math.ceil(2.1)", Value::Integer(3)),
+				("math.round(2.5)", Value::Integer(3)),
+				("math.round(7)", Value::Integer(7)),
+				("modifier(7)", Value::Integer(-2)),
+				("math.min(3, 1.5, 2)", Value::Decimal(1.5)),
+				("math.max(3, 1.5, 2)", Value::Integer(3)),
+				("math.clamp(12, 0, 10)", Value::Integer(10)),
+				("math.clamp(-2, 0, 10)", Value::Integer(0)),
+				("math.clamp(4, 0, 10)", Value::Integer(4)),
+				("math.sign(-4)", Value::Integer(-1)),
+				("math.sign(0)", Value::Integer(0)),
+				("math.log(100, 10)", Value::Decimal(2.0)),
+				("math.log(1)", Value::Decimal(0.0)),
+				("area(1)", Value::Decimal(std::f32::consts::PI)),
+			],
+		);
+		assert!((1..=6).contains(
+			&runtime
+				.run_val("math.max(1d6, 1)")
+				.unwrap()
+				.to_integer()
+				.unwrap()
+		));
+		assert!(runtime.run_val("math.sqrt(1, 2)").is_err());
+		assert!(runtime.run_val("math.max()").is_err());
+		assert!(runtime.run_val("math.sqrt(\"a\")").is_err());
+		assert!(runtime.run_val("math.sqrt").is_err());
+		assert!(matches!(
+			runtime.run_val("math.abs(0 - 2147483647 - 1)"),
+			Err(RuntimeError::BadStdFnCall(_))
+		));
+		assert!(runtime.run_val("math.max([1, 2])").is_err());
+		assert!(runtime.run_val("math.min(\"a\")").is_err());
+		assert!(runtime.run_val("math.max(3, \"a\")").is_err());
+		assert!(matches!(
+			runtime.run_val("math.cbrt(8)"),
+			Err(RuntimeError::PropNotFound(_))
+		));
+	}
+
+	#[test]
+	fn math_value() {
+		let mut runtime = Runtime::new(rand::thread_rng(), ());
+		runtime
+			.load(
+				r#"
+			m = math;
+			root = ns x -> ns.sqrt(x);
+			"#,
+				"",
+			)
+			.unwrap();
+		assert_cases(
+			&runtime,
+			&[
+				("m.sqrt(4)", Value::Decimal(2.0)),
+				("m.pi", Value::Decimal(std::f32::consts::PI)),
+				("root(math, 9)", Value::Decimal(3.0)),
+				("math == m", Value::Boolean(true)),
+			],
+		);
+		assert!(matches!(runtime.run_val("m").unwrap(), Value::Math));
+		assert!(matches!(
+			runtime.run_val("m.cbrt(8)"),
+			Err(RuntimeError::PropNotFound(_))
+		));
+		assert!(runtime.run_val("m.sqrt").is_err());
+		assert!(runtime.check().is_empty());
+	}
+
+	#[test]
+	fn shadowing_math() {
+		let mut runtime = Runtime::new(rand::thread_rng(), ());
+		runtime.load("math = { pi: 3 };", "").unwrap();
+		assert_eq!(runtime.run_val("math.pi").unwrap(), Value::Integer(3));
+	}
+
+	#[test]
+	fn math_types() {
+		let runtime = test_runtime();
+		let checker = Checker::new(&runtime);
+		assert_eq!(
+			checker.variable("modifier"),
+			Type::Function(vec![Type::Any], Box::new(Type::Integer))
+		);
+		assert_eq!(
+			checker.variable("hypot"),
+			Type::Function(vec![Type::Any, Type::Any], Box::new(Type::Decimal))
+		);
+		let mut runtime = Runtime::new(rand::thread_rng(), ());
+		runtime
+			.load("a = math.sqrt(\"4\"); b = math.tan(1);", "")
+			.unwrap();
+		assert_eq!(runtime.check().len(), 2);
+	}
+}
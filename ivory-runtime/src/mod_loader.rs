@@ -1,13 +1,71 @@
 use std::collections::{HashMap, HashSet};
 
 use crate::{error::ModLoaderError, runtime::RuntimeValues, Result};
+use ivory_expression::{Expression, ExpressionComponent, Pair};
 use ivory_tokenizer::{
+	accessor::{Accessor, AccessorComponent, AccessorRoot},
+	expression::{math::ExprOpMath, ExpressionToken, Op},
+	ienum::EnumDefinition,
 	module::iuse::{Froms, Use},
+	table::TableRoll,
 	tokenize,
+	values::{
+		function::FunctionValue, match_value::MatchPattern, string::StringValue,
+		Value,
+	},
 	variable::Variable,
 	Module,
 };
 
+/// The module every runtime imports everything from, before any others
+pub const PRELUDE: &str = "std:prelude";
+
+/// Modules that come with Ivory, used like `use * from "std:dice"`. They're
+/// never loaded by a `ModLoader`.
+const STD_MODULES: &[(&str, &str)] = &[
+	(PRELUDE, include_str!("../std/prelude.ivory")),
+	("std:dice", include_str!("../std/dice.ivory")),
+	("std:dnd", include_str!("../std/dnd.ivory")),
+];
+
+fn is_std(url: &str) -> bool {
+	url.starts_with("std:")
+}
+
+/// Loads the source of a module, from the modules that come with Ivory if it's
+/// one of them, otherwise with the loader.
+pub fn load(
+	loader: &mut Box<dyn ModLoader>,
+	url: &str,
+	parent_path: &str,
+) -> std::result::Result<String, ModLoaderError> {
+	if !is_std(url) {
+		return loader.load(url, parent_path);
+	}
+	STD_MODULES
+		.iter()
+		.find(|(name, _)| *name == url)
+		.map(|(_, src)| src.to_string())
+		.ok_or_else(|| ModLoaderError::StdModuleNotFound(url.to_string()))
+}
+
+/// Replaces the source of a module with the loader. The modules that come
+/// with Ivory can't be changed.
+pub fn rewrite(
+	loader: &mut Box<dyn ModLoader>,
+	url: &str,
+	parent_path: &str,
+	new_value: &str,
+) -> std::result::Result<(), ModLoaderError> {
+	if is_std(url) {
+		return Err(ModLoaderError::CannotRewrite(
+			url.to_string(),
+			"standard modules are part of Ivory".to_string(),
+		));
+	}
+	loader.rewrite(url, parent_path, new_value)
+}
+
 pub trait ModLoader {
 	fn load(
 		&mut self,
@@ -15,11 +73,12 @@ pub trait ModLoader {
 		parent_path: &str,
 	) -> std::result::Result<String, ModLoaderError>;
 
+	/// Replaces the source of a module with `new_value`
 	fn rewrite(
 		&mut self,
-		url: &str,
-		parent_path: &str,
-		new_value: &str,
+		_url: &str,
+		_parent_path: &str,
+		_new_value: &str,
 	) -> std::result::Result<(), ModLoaderError> {
 		Err(ModLoaderError::RewriteNotImplemented)
 	}
@@ -39,10 +98,22 @@ impl ModLoader for () {
 	}
 }
 
+/// Where the source of a module was loaded from, so it can be loaded again.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModuleSource {
+	pub url: String,
+	pub parent: String,
+}
+
+#[derive(Clone)]
 pub struct LoadedModule {
 	pub values: RuntimeValues,
 	/// maps aliases to real variable names within the module
 	pub froms: ModuleImports,
+	/// The use statement that loaded this module
+	pub using: Use,
+	/// The path of the module containing the use statement
+	pub parent: String,
 }
 
 impl LoadedModule {
@@ -51,12 +122,14 @@ impl LoadedModule {
 		src: &Use,
 		parent: &str,
 	) -> Result<Self> {
-		Ok(Self {
+		let mut module = Self {
 			values: RuntimeValues::new(
-				tokenize::<Module>(&loader.load(src.path.0.as_str(), parent)?)?,
+				tokenize::<Module>(&load(loader, src.path.0.as_str(), parent)?)?,
 				src.path.0.as_str(),
 				loader,
 			)?,
+			using: src.clone(),
+			parent: parent.to_string(),
 			froms: match &src.froms {
 				Froms::Asterix => ModuleImports::Asterix,
 				Froms::Variables(froms) => ModuleImports::Aliases(
@@ -71,7 +144,61 @@ impl LoadedModule {
 						.collect(),
 				),
 			},
-		})
+		};
+		module.qualify_hidden();
+		Ok(module)
+	}
+	/// Imports everything from the prelude
+	pub fn prelude() -> Self {
+		let using = Use {
+			froms: Froms::Asterix,
+			path: StringValue(PRELUDE.to_string()),
+		};
+		let mut loader: Box<dyn ModLoader> = Box::new(());
+		Self::new(&mut loader, &using, "").expect("the prelude is valid Ivory")
+	}
+
+	pub fn is_prelude(&self) -> bool {
+		self.using.path.0 == PRELUDE
+	}
+
+	/// Loads this module's source again, keeping the modules it uses that
+	/// haven't changed.
+	pub fn reload(&mut self, loader: &mut Box<dyn ModLoader>) -> Result<()> {
+		let url = self.using.path.0.as_str();
+		let src = load(loader, url, &self.parent)?;
+		self.values.reload(tokenize::<Module>(&src)?, url, loader)?;
+		self.qualify_hidden();
+		Ok(())
+	}
+
+	/// Variables imported by name can still use the rest of their module,
+	/// which the importing module can't see. References to those are
+	/// qualified with the module's path, like `./lib.iv:helper`.
+	pub fn qualify_hidden(&mut self) {
+		let aliases = match &self.froms {
+			ModuleImports::Asterix => return,
+			ModuleImports::Aliases(aliases) => aliases,
+		};
+		let hidden = self
+			.values
+			.variable_names()
+			.into_iter()
+			.filter(|name| aliases.get(name) != Some(name))
+			.collect();
+		let qualifier = Qualifier {
+			path: &self.using.path.0,
+			hidden: &hidden,
+		};
+		qualifier.values(&mut self.values);
+	}
+
+	/// The name a variable imported from this module has inside of it
+	pub fn real_name(&self, name: &str) -> Option<String> {
+		match &self.froms {
+			ModuleImports::Asterix => Some(name.to_string()),
+			ModuleImports::Aliases(aliases) => aliases.get(name).cloned(),
+		}
 	}
 	pub fn get_variable(&self, name: &str) -> Option<&Variable> {
 		match &self.froms {
@@ -82,6 +209,12 @@ impl LoadedModule {
 				.flatten(),
 		}
 	}
+	pub fn enum_definition(&self, name: &str) -> Option<&EnumDefinition> {
+		self.values.enum_definition(&self.real_name(name)?)
+	}
+	pub fn table(&self, name: &str) -> Option<&TableRoll> {
+		self.values.table(&self.real_name(name)?)
+	}
 	pub fn variable_names(&self) -> HashSet<String> {
 		match &self.froms {
 			ModuleImports::Asterix => self.values.variable_names(),
@@ -90,6 +223,118 @@ impl LoadedModule {
 	}
 }
 
+/// Renames references to the `hidden` variables of a module to `path:name`
+struct Qualifier<'a> {
+	path: &'a str,
+	hidden: &'a HashSet<String>,
+}
+
+impl<'a> Qualifier<'a> {
+	fn values(&self, values: &mut RuntimeValues) {
+		for variable in values.variables.values_mut() {
+			self.expr(&mut variable.value, &HashSet::new());
+		}
+		for module in values.loaded_modules.iter_mut() {
+			self.values(&mut module.values);
+		}
+	}
+
+	fn expr(
+		&self,
+		expr: &mut Expression<Op, ExpressionToken>,
+		bound: &HashSet<String>,
+	) {
+		self.component(&mut expr.first, bound);
+		for Pair(op, component) in expr.pairs.iter_mut() {
+			if let Op::Math(ExprOpMath::Ternary(ternary)) = op {
+				self.expr(ternary, bound);
+			}
+			self.component(component, bound);
+		}
+	}
+
+	fn component(
+		&self,
+		component: &mut ExpressionComponent<Op, ExpressionToken>,
+		bound: &HashSet<String>,
+	) {
+		match component {
+			ExpressionComponent::Token(ExpressionToken(accessor)) => {
+				self.accessor(accessor, bound)
+			}
+			ExpressionComponent::Paren(paren) => self.expr(paren, bound),
+		}
+	}
+
+	fn accessor(
+		&self,
+		Accessor(root, components): &mut Accessor,
+		bound: &HashSet<String>,
+	) {
+		match root {
+			AccessorRoot::Variable(name) => {
+				if self.hidden.contains(&name.0) && !bound.contains(&name.0) {
+					name.0 = format!("{}:{}", self.path, name.0);
+				}
+			}
+			AccessorRoot::Value(value) => self.value(value, bound),
+		}
+		for component in components.iter_mut() {
+			match component {
+				AccessorComponent::Index(i) => self.expr(i, bound),
+				AccessorComponent::Call(args) => {
+					for arg in args.iter_mut() {
+						self.expr(arg, bound);
+					}
+				}
+				AccessorComponent::Property(_)
+				| AccessorComponent::OptionalProperty(_) => {}
+			}
+		}
+	}
+
+	fn value(&self, value: &mut Value, bound: &HashSet<String>) {
+		match value {
+			Value::Array(array) => {
+				for v in array.0.iter_mut() {
+					self.expr(v, bound);
+				}
+			}
+			Value::Object(object) => {
+				for v in object.0.values_mut() {
+					self.expr(v, bound);
+				}
+			}
+			Value::Struct(instance) => {
+				for v in instance.values.values_mut() {
+					self.expr(v, bound);
+				}
+			}
+			Value::Match(m) => {
+				self.expr(&mut m.value, bound);
+				for arm in m.arms.iter_mut() {
+					let mut bound = bound.clone();
+					if let MatchPattern::Variant(_, bindings) = &arm.pattern {
+						bound.extend(bindings.iter().map(|b| b.0.clone()));
+					}
+					self.expr(&mut arm.expr, &bound);
+				}
+			}
+			Value::Function(FunctionValue { args, expr, .. }) => {
+				let mut bound = bound.clone();
+				bound.extend(args.iter().map(|a| a.0.clone()));
+				self.expr(expr, &bound);
+			}
+			Value::Boolean(_)
+			| Value::Decimal(_)
+			| Value::Integer(_)
+			| Value::String(_)
+			| Value::None(_) => {}
+		}
+	}
+}
+
+#[derive(Clone)]
 pub enum ModuleImports {
 	Asterix,
 	/// Nickname -> Real name
@@ -99,7 +344,12 @@ pub enum ModuleImports {
 #[cfg(test)]
 mod test {
 
-	use crate::runtime::Runtime;
+	use std::{cell::RefCell, rc::Rc};
+
+	use crate::{
+		runtime::{assert_cases, Runtime},
+		value::Value,
+	};
 
 	use super::*;
 
@@ -107,6 +357,10 @@ mod test {
 		r#"a_foo = 10; a_bar = 20;"#,
 		r#"b_foo = "ten"; b_bar = "twenty";"#,
 		r#"c_foo = 5 + 5; c_bar = some -> some + 20;"#,
+		r#"d_loop = looped + 1;"#,
+		"table loot d4\n| roll | item |\n| 1-3 | \"Copper\" |\n| 4 | \"Gold\" |\n",
+		"enum Dmg { Fire, Cold }\nweakness = d -> match d { Fire => 2, Cold => 0 };",
+		r#"g_base = 3; g_helper = x -> str + g_base + x; g_bonus = g_helper(1);"#,
 	];
 
 	struct DummyLoader;
@@ -121,6 +375,10 @@ mod test {
 				"a" => MODS[0].to_string(),
 				"b" => MODS[1].to_string(),
 				"c" => MODS[2].to_string(),
+				"d" => MODS[3].to_string(),
+				"e" => MODS[4].to_string(),
+				"f" => MODS[5].to_string(),
+				"g" => MODS[6].to_string(),
 				_ => unreachable!(),
 			})
 		}
@@ -160,6 +418,85 @@ mod test {
 		dummy_runtime_ok(m2, "good");
 		dummy_runtime_err(m2, "bad");
 	}
+	#[test]
+	fn module_tables() {
+		let mut runtime = Runtime::new(rand::thread_rng(), DummyLoader);
+		runtime
+			.load(
+				r#"
+		use * from "e";
+		use loot as treasure from "e";
+		found = loot.roll().item;
+		"#,
+				"",
+			)
+			.unwrap();
+		for roll in &["found", "loot.roll().item", "treasure.roll().item"] {
+			assert!(matches!(
+				runtime.run_val(roll).unwrap(),
+				Value::String(item) if item == "Copper" || item == "Gold"
+			));
+		}
+		assert!(!runtime.graph.is_pure("found"));
+		assert!(runtime.check().is_empty());
+	}
+
+	#[test]
+	fn module_enums() {
+		let mut runtime = Runtime::new(rand::thread_rng(), DummyLoader);
+		runtime
+			.load(
+				r#"
+		use * from "f";
+		burn = weakness(Dmg.Fire);
+		resist = (d: Dmg) -> match d { Fire => 0, Cold => 1 };
+		"#,
+				"",
+			)
+			.unwrap();
+		assert_eq!(
+			format!("{}", runtime.run_val("Dmg.Fire").unwrap()),
+			"Dmg.Fire"
+		);
+		assert_eq!(runtime.run_val("burn").unwrap(), Value::Integer(2));
+		assert_eq!(
+			runtime.run_val("resist(Dmg.Cold)").unwrap(),
+			Value::Integer(1)
+		);
+		assert!(runtime.run_val("Dmg.Acid").is_err());
+		assert!(runtime.check().is_empty());
+	}
+
+	#[test]
+	fn unexported_variables() {
+		let mut runtime = Runtime::new(rand::thread_rng(), DummyLoader);
+		runtime
+			.load(
+				r#"
+		use g_bonus as bonus from "g";
+		str = 16;
+		total = bonus + 1;
+		"#,
+				"",
+			)
+			.unwrap();
+		assert_eq!(runtime.run_val("total").unwrap(), Value::Integer(21));
+		// the rest of the module is only used by what it exports
+		assert!(runtime.run_val("g_helper(1)").is_err());
+		assert!(runtime.run_val("g_base").is_err());
+		let dependents = runtime.dependents("str").unwrap();
+		assert_eq!(
+			dependents.names().into_iter().collect::<Vec<_>>(),
+			vec!["bonus", "g:g_helper", "total"]
+		);
+		assert!(runtime
+			.dependencies("total")
+			.unwrap()
+			.names()
+			.contains("g:g_base"));
+		assert!(runtime.check().is_empty());
+	}
+
 	#[test]
 	fn load_alias() {
 		let m = r#"
@@ -188,4 +525,226 @@ mod test {
 		"#;
 		dummy_runtime_ok(m2, "c_foo");
 	}
+
+	/// Loads modules from a set of files that can be changed during a test,
+	/// keeping track of every url that gets loaded.
+	#[derive(Clone, Default)]
+	struct FileSet {
+		files: Rc<RefCell<HashMap<String, String>>>,
+		loads: Rc<RefCell<Vec<String>>>,
+	}
+
+	impl FileSet {
+		fn set(&self, url: &str, src: &str) {
+			self
+				.files
+				.borrow_mut()
+				.insert(url.to_string(), src.to_string());
+		}
+		fn loads(&self) -> Vec<String> {
+			self.loads.borrow_mut().drain(..).collect()
+		}
+	}
+
+	impl ModLoader for FileSet {
+		fn load(
+			&mut self,
+			url: &str,
+			_: &str,
+		) -> std::result::Result<String, ModLoaderError> {
+			self.loads.borrow_mut().push(url.to_string());
+			Ok(self.files.borrow()[url].clone())
+		}
+
+		fn rewrite(
+			&mut self,
+			url: &str,
+			_: &str,
+			new_value: &str,
+		) -> std::result::Result<(), ModLoaderError> {
+			self.set(url, new_value);
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn incremental_updates() {
+		let files = FileSet::default();
+		files.set("a", MODS[0]);
+		files.set("c", MODS[2]);
+		files.set(
+			"root",
+			r#"
+		use * from "a";
+		use c_foo from "c";
+		x = a_foo + 1;
+		y = x * 2;
+		z = c_foo;
+		"#,
+		);
+		let mut runtime = Runtime::new(rand::thread_rng(), files.clone());
+		runtime.load_path("root", "").unwrap();
+		assert_eq!(files.loads(), vec!["root", "a", "c"]);
+
+		let cached = |runtime: &Runtime| {
+			let mut names = runtime
+				.values
+				.cache
+				.borrow()
+				.keys()
+				.cloned()
+				.collect::<Vec<String>>();
+			names.sort();
+			names
+		};
+
+		runtime.run("y + z").unwrap();
+		assert_eq!(cached(&runtime), vec!["a_foo", "c_foo", "x", "y", "z"]);
+
+		runtime.update_variable("x", "a_foo + 2").unwrap();
+		assert_eq!(cached(&runtime), vec!["a_foo", "c_foo", "z"]);
+		assert_eq!(runtime.run_val("y").unwrap(), Value::Integer(24));
+
+		// a variable that would depend on itself is rejected
+		assert!(runtime.update_variable("a_foo", "y").is_err());
+		assert_eq!(runtime.run_val("y").unwrap(), Value::Integer(24));
+
+		files.set("a", "a_foo = 100; a_bar = 20;");
+		runtime.reload_module("a").unwrap();
+		assert_eq!(files.loads(), vec!["a"]);
+		assert_eq!(cached(&runtime), vec!["c_foo", "z"]);
+		assert_eq!(runtime.run_val("y").unwrap(), Value::Integer(204));
+
+		files.set(
+			"root",
+			r#"
+		use * from "a";
+		use c_foo from "c";
+		x = a_foo + 2;
+		y = x * 2;
+		z = c_foo + 1;
+		"#,
+		);
+		runtime.reload_module("root").unwrap();
+		assert_eq!(files.loads(), vec!["root"]);
+		assert_eq!(cached(&runtime), vec!["a_foo", "c_foo", "x", "y"]);
+		assert_eq!(runtime.run_val("z").unwrap(), Value::Integer(11));
+
+		assert!(runtime.reload_module("not_loaded").is_err());
+	}
+
+	#[test]
+	fn edit_variables() {
+		let files = FileSet::default();
+		files.set("a", "a_foo = 10; # the foo\na_bar = 20;");
+		files.set("b", "use a_foo as foo from \"a\";");
+		files.set(
+			"root",
+			"use foo as bar from \"b\";\nx = 1 + # one\n\t2; # x\ny = x + bar;",
+		);
+		let mut runtime = Runtime::new(rand::thread_rng(), files.clone());
+		runtime.load_path("root", "").unwrap();
+		assert_eq!(runtime.run_val("y").unwrap(), Value::Integer(13));
+
+		runtime.edit_variable("x", "5;").unwrap();
+		assert_eq!(
+			files.files.borrow()["root"],
+			"use foo as bar from \"b\";\nx = 5; # x\ny = x + bar;"
+		);
+		assert_eq!(runtime.run_val("y").unwrap(), Value::Integer(15));
+
+		runtime.edit_variable("bar", "4 * 5").unwrap();
+		assert_eq!(
+			files.files.borrow()["a"],
+			"a_foo = 4 * 5; # the foo\na_bar = 20;"
+		);
+		assert_eq!(runtime.run_val("y").unwrap(), Value::Integer(25));
+
+		assert!(runtime.edit_variable("x", "5 +").is_err());
+		assert!(runtime.edit_variable("not_defined", "5").is_err());
+		assert_eq!(runtime.run_val("y").unwrap(), Value::Integer(25));
+	}
+
+	#[test]
+	fn cycle_through_alias() {
+		let mut runtime = Runtime::new(rand::thread_rng(), DummyLoader);
+		let res = runtime.load(
+			r#"
+		use d_loop as aliased from "d";
+
+		looped = aliased * 2;
+		"#,
+			"",
+		);
+		match res {
+			Err(crate::RuntimeError::VariableCycle(chain)) => {
+				assert_eq!(chain, vec!["aliased", "looped", "aliased"])
+			}
+			other => panic!("Expected a variable cycle, got {:?}", other.err()),
+		}
+	}
+
+	#[test]
+	fn std_modules() {
+		let files = FileSet::default();
+		files.set(
+			"root",
+			r#"
+		use * from "std:dnd";
+		use advantage from "std:dice";
+		str = 16;
+		str_mod = str.modifier();
+		mean = [1, 2, 3, 4].avg();
+		clamp = 7;
+		"#,
+		);
+		let mut runtime = Runtime::new(rand::thread_rng(), files.clone());
+		assert_eq!(
+			runtime.run_val("[2, 3].avg()").unwrap(),
+			Value::Decimal(2.5)
+		);
+		runtime.load_path("root", "").unwrap();
+		// standard modules are never loaded by the loader
+		assert_eq!(files.loads(), vec!["root"]);
+		assert!(runtime.check().is_empty());
+		assert_eq!(runtime.run_val("str_mod").unwrap(), Value::Integer(3));
+		assert_cases(
+			&runtime,
+			&[
+				("proficiency(5)", Value::Integer(3)),
+				("modifier(15)", Value::Integer(2)),
+				("modifier(9)", Value::Integer(-1)),
+				("modifier(1)", Value::Integer(-5)),
+				("modifier(20)", Value::Integer(5)),
+				("proficiency(1)", Value::Integer(2)),
+				("proficiency(4)", Value::Integer(2)),
+				("proficiency(6)", Value::Integer(3)),
+				("proficiency(12)", Value::Integer(4)),
+				("proficiency(17)", Value::Integer(6)),
+				("proficiency(20)", Value::Integer(6)),
+			],
+		);
+		assert_eq!(runtime.run_val("mean").unwrap(), Value::Decimal(2.5));
+		assert!((2..=20)
+			.contains(&runtime.run_val("advantage").unwrap().to_integer().unwrap()));
+		assert!(runtime.run_val("disadvantage").is_err());
+		// sheets replace what's in the prelude
+		assert_eq!(runtime.run_val("clamp").unwrap(), Value::Integer(7));
+
+		runtime.reload_module("root").unwrap();
+		assert_eq!(runtime.run_val("mean").unwrap(), Value::Decimal(2.5));
+
+		assert!(matches!(
+			runtime.edit_variable("modifier", "1"),
+			Err(crate::RuntimeError::ModLoader(
+				ModLoaderError::CannotRewrite(..)
+			))
+		));
+		assert!(matches!(
+			runtime.load("use * from \"std:missing\";", ""),
+			Err(crate::RuntimeError::ModLoader(
+				ModLoaderError::StdModuleNotFound(_)
+			))
+		));
+	}
 }
@@ -84,7 +84,26 @@ impl SingleRoll {
 	}
 }
 
+/// A random choice made by a standard function like `.pick()`, along with
+/// the rolls that made it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Draw {
+	/// How the function was called, like `pick(2)`
+	pub name: String,
+	pub rolls: Vec<Roll>,
+	pub value: Value,
+}
+
 impl Roll {
+	/// A single die
+	pub fn die(runtime: &Runtime, sides: u32) -> Self {
+		Roll {
+			count: 1,
+			sides,
+			rolls: vec![SingleRoll::new_rolled(runtime, sides)],
+		}
+	}
+
 	pub fn create(
 		runtime: &Runtime,
 		count: &Value,
@@ -134,6 +153,23 @@ impl Display for Roll {
 	}
 }
 
+impl Display for Draw {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"{}: {} -> {}",
+			self.name,
+			self
+				.rolls
+				.iter()
+				.map(|r| format!("{}", r))
+				.collect::<Vec<String>>()
+				.join(", "),
+			self.value
+		)
+	}
+}
+
 fn do_cmp(a: u32, cmp: &Comparator, b: u32) -> bool {
 	match cmp {
 		Comparator::Gt => a > b,
@@ -1,30 +1,35 @@
 use crate::{
-	expr::{into_prec, RolledOp},
-	mod_loader::{LoadedModule, ModLoader},
+	check::{Checker, Diagnostic},
+	enum_value::EnumValue,
+	expr::{into_prec, Precedence, RolledOp},
+	graph::{as_function, DependencyGraph, DependencyTree},
+	math::MathLibrary,
+	mod_loader::{self, LoadedModule, ModLoader, ModuleSource},
+	prec::Climber,
 	prec::{self, Token},
-	prec::{Assoc, Climber},
-	roll::Roll,
-	std_fns::StdFnLibrary,
+	roll::{Draw, Roll},
+	std_fns::{NativeFn, StdFnLibrary},
+	table,
 	value::Value,
 	Result, RuntimeError,
 };
 use ivory_expression::{Expression, ExpressionComponent};
 use ivory_tokenizer::{
 	accessor::{Accessor, AccessorComponent, AccessorRoot},
-	expression::{
-		math::{ExprOpMath, ExprOpMathKind},
-		ExpressionToken, Op,
-	},
-	istruct::StructDefinition,
+	expression::{math::ExprOpMath, ExpressionToken, Op},
+	ienum::EnumDefinition,
+	istruct::{StructDefinition, StructDefinitionValue},
+	module::find_variable_expression,
+	table::TableRoll,
 	tokenize,
 	values::function::FunctionValue,
-	variable::Variable,
+	variable::{Variable, VariableName},
 	Module,
 };
 use rand::RngCore;
 use std::{
 	cell::{RefCell, RefMut},
-	collections::{BTreeMap, HashSet},
+	collections::{BTreeMap, HashMap, HashSet},
 };
 
 type Component = ExpressionComponent<RolledOp, Value>;
@@ -42,6 +47,15 @@ pub struct Runtime {
 	>,
 	pub mod_loader: Box<dyn ModLoader>,
 	pub std_fns: StdFnLibrary,
+	/// The `math` namespace
+	pub math: MathLibrary,
+	pub graph: DependencyGraph,
+	/// Where the root module was loaded from, if it was loaded with a path
+	pub root: Option<ModuleSource>,
+	/// Don't turn values into strings implicitly, like in `"Str: " + 3`
+	pub strict: bool,
+	/// Random choices made while running the last expression, like `.pick()`
+	pub draws: RefCell<Vec<Draw>>,
 }
 
 impl Runtime {
@@ -63,48 +77,159 @@ impl Runtime {
 		rng: R,
 		mod_loader: L,
 	) -> Self {
-		let climber = Climber::new(
-			|op, _, _| match op {
-				RolledOp::Ternary(_) => (0, Assoc::Right),
-				RolledOp::Logic(_) => (1, Assoc::Left),
-				RolledOp::Comparator(_) => (2, Assoc::Left),
-				RolledOp::Math { kind, .. } => match kind {
-					ExprOpMathKind::Add | ExprOpMathKind::Sub => (3, Assoc::Left),
-					ExprOpMathKind::Mul | ExprOpMathKind::Div => (4, Assoc::Right),
-				},
-			},
-			Self::prec_handler,
-		);
+		let climber =
+			Climber::new(|op, _, _| Precedence::from(op).rule(), Self::prec_handler);
 		Self {
 			values: RuntimeValues {
 				structs: BTreeMap::new(),
+				enums: BTreeMap::new(),
+				tables: BTreeMap::new(),
+				methods: BTreeMap::new(),
 				variables: BTreeMap::new(),
-				loaded_modules: Vec::new(),
+				loaded_modules: vec![LoadedModule::prelude()],
+				cache: RefCell::new(HashMap::new()),
 			},
 			rng: RefCell::new(Box::new(rng)),
 			climber,
 			mod_loader: Box::new(mod_loader),
 			std_fns: StdFnLibrary::new(),
+			math: MathLibrary::new(),
+			graph: DependencyGraph::default(),
+			root: None,
+			strict: false,
+			draws: RefCell::new(Vec::new()),
 		}
 	}
+	/// Adds a function that can be called from Ivory, either on a value like
+	/// `x.name()` or like `name(x)`. It replaces any standard function with the
+	/// same name.
+	pub fn register_fn(&mut self, name: &str, f: NativeFn) {
+		self.std_fns.native.insert(name.to_string(), f);
+		// variables that use the function might not be pure anymore
+		self.graph = DependencyGraph::new(&self.values, &self.std_fns);
+		self.values.cache.borrow_mut().clear();
+	}
+
 	pub fn rng(&self) -> RefMut<Box<dyn RngCore>> {
 		self.rng.borrow_mut()
 	}
 	pub fn load(&mut self, input: &str, path: &str) -> Result<()> {
-		self.values = RuntimeValues::new(
+		let mut values = RuntimeValues::new(
 			tokenize::<Module>(input)?,
 			path,
 			&mut self.mod_loader,
 		)?;
+		values.loaded_modules.insert(0, LoadedModule::prelude());
+		let graph = DependencyGraph::new(&values, &self.std_fns);
+		graph.check_cycles()?;
+		self.values = values;
+		self.graph = graph;
+		self.root = None;
 		Ok(())
 	}
 
 	pub fn load_path(&mut self, path: &str, root_path: &str) -> Result<()> {
-		let s = self.mod_loader.load(path, root_path)?;
-		self.load(&s, root_path)
+		let s = mod_loader::load(&mut self.mod_loader, path, root_path)?;
+		self.load(&s, root_path)?;
+		self.root = Some(ModuleSource {
+			url: path.to_string(),
+			parent: root_path.to_string(),
+		});
+		Ok(())
+	}
+
+	/// Changes the definition of a single variable in the root module, without
+	/// touching its source.
+	pub fn update_variable(&mut self, name: &str, value: &str) -> Result<()> {
+		let mut values = self.values.clone();
+		values.variables.insert(
+			name.to_string(),
+			Variable {
+				name: VariableName::new(name),
+				value: tokenize::<Expression<Op, ExpressionToken>>(value)?,
+			},
+		);
+		self.replace_values(values)
+	}
+
+	/// Loads a module's source again, given the url it was loaded with. This
+	/// can be the root module, or any module loaded by a use statement.
+	pub fn reload_module(&mut self, path: &str) -> Result<()> {
+		let mut values = self.values.clone();
+		match &self.root {
+			Some(root) if root.url == path || root.parent == path => {
+				let src =
+					mod_loader::load(&mut self.mod_loader, &root.url, &root.parent)?;
+				values.reload(
+					tokenize::<Module>(&src)?,
+					&root.parent,
+					&mut self.mod_loader,
+				)?;
+			}
+			_ => {
+				if !values.reload_modules(path, &mut self.mod_loader)? {
+					return Err(RuntimeError::ModuleNotLoaded(path.to_string()));
+				}
+			}
+		}
+		self.replace_values(values)
+	}
+
+	/// Swaps in changed definitions, only forgetting the cached values of
+	/// variables that changed and everything that depends on them.
+	fn replace_values(&mut self, values: RuntimeValues) -> Result<()> {
+		let graph = DependencyGraph::new(&values, &self.std_fns);
+		graph.check_cycles()?;
+
+		let mut cache = self.values.cache.take();
+		if self.values.structs != values.structs
+			|| self.values.enums != values.enums
+			|| self.values.tables != values.tables
+			|| self.values.methods != values.methods
+		{
+			cache.clear();
+		}
+		let names = self.values.variable_names();
+		for name in names.union(&values.variable_names()) {
+			if self.values.get_variable(name) != values.get_variable(name) {
+				cache.remove(name);
+				for g in &[&self.graph, &graph] {
+					for dependent in g.all_dependents(name) {
+						cache.remove(dependent);
+					}
+				}
+			}
+		}
+
+		self.values = values;
+		self.values.cache = RefCell::new(cache);
+		self.graph = graph;
+		Ok(())
+	}
+
+	/// Everything a variable depends on, including variables only used in
+	/// the bodies of functions it calls.
+	pub fn dependencies(&self, name: &str) -> Result<DependencyTree> {
+		self.graph_node_exists(name)?;
+		Ok(self.graph.dependency_tree(name))
+	}
+
+	/// Everything that would change if a variable's value changed.
+	pub fn dependents(&self, name: &str) -> Result<DependencyTree> {
+		self.graph_node_exists(name)?;
+		Ok(self.graph.dependent_tree(name))
+	}
+
+	fn graph_node_exists(&self, name: &str) -> Result<()> {
+		if self.graph.nodes.contains_key(name) {
+			Ok(())
+		} else {
+			Err(RuntimeError::VariableNotFound(name.to_string()))
+		}
 	}
 
 	pub fn run(&self, cmd: &str) -> Result<Expression<RolledOp, Value>> {
+		self.draws.borrow_mut().clear();
 		let ex = tokenize::<Expression<Op, ExpressionToken>>(cmd)?;
 		Ok(self.execute(&RuntimeContext::new(), &ex)?)
 	}
@@ -119,65 +244,110 @@ impl Runtime {
 		ctx: &RuntimeContext,
 		Accessor(var, components): &Accessor,
 	) -> Result<Expression<Op, Value>> {
+		let mut components = components.iter();
+		// The name of whatever is being called, for errors
+		let mut callee = match var {
+			AccessorRoot::Variable(variable) => variable.0.as_str(),
+			AccessorRoot::Value(_) => "function",
+		};
 		let mut expr = match var {
 			AccessorRoot::Variable(variable) => match ctx.params.get(&variable.0) {
 				Some(param) => param.clone(),
-				None => {
-					let val = self.values.get_variable(&variable.0).ok_or_else(|| {
-						RuntimeError::VariableNotFound(variable.0.clone())
-					})?;
-					self.valueify(&RuntimeContext::new(), &val.value)?
-				}
+				None => match self.values.get_variable(&variable.0) {
+					Some(val)
+						if table::is_roll(components.as_slice())
+							&& self.values.table(&variable.0).is_some() =>
+					{
+						components.nth(1);
+						self.roll_table(&variable.0, val)?
+					}
+					Some(val) if self.graph.is_pure(&variable.0) => {
+						Expression::new(self.cached_value(&variable.0, val)?)
+					}
+					Some(val) => self.valueify(&RuntimeContext::new(), &val.value)?,
+					None => match self.values.enum_definition(&variable.0) {
+						Some(def) => self.enum_variant(ctx, def, &mut components)?,
+						None if variable.0 == "math" => Expression::new(Value::Math),
+						None => self.free_std_call(ctx, &variable.0, components.next())?,
+					},
+				},
 			},
 			AccessorRoot::Value(value) => {
 				Expression::<Op, _>::new(Value::from_token(value, self, ctx)?)
 			}
 		};
-		// This is a check that is set to something when the accessor is:
-		// a variable that is not found on the parent value
-		// is the name of a standard function
-		let mut std_fn_call: Option<String> = None;
+		// This is set when the accessor is a function that isn't a value: a
+		// method of a struct, or a standard function
+		let mut pending_call: Option<PendingCall> = None;
+		// Set when the accessor is a field of a struct, which is called with the
+		// other fields in scope if it's a function
+		let mut field_scope: Option<RuntimeContext> = None;
 		for component in components {
-			// make sure that a call always comes right after a std function name
-			if std_fn_call.is_some()
-				&& !matches!(component, AccessorComponent::Call(_))
-			{
-				return Err(RuntimeError::BadStdFnCall(format!(
-					"{} is a standard function, not a value, and must be called",
-					std_fn_call.unwrap()
-				)));
+			let scope = field_scope.take();
+			// make sure that a call always comes right after a method name
+			if let Some(pending) = &pending_call {
+				if !matches!(component, AccessorComponent::Call(_)) {
+					return Err(pending.uncalled());
+				}
 			}
 			let previous_value = self.val_expr_collapse(ctx, &expr)?;
+			// `?.` on none skips the rest of the accessor
+			if let (AccessorComponent::OptionalProperty(_), Value::None) =
+				(component, &previous_value)
+			{
+				return Ok(Expression::new(Value::None));
+			}
 			match component {
-				AccessorComponent::Property(prop) => {
-					if let Value::Object(obj) = &previous_value {
-						if let Some(p) = obj.get(&prop.0) {
-							expr = Expression::new(p.clone());
-						} else {
-							if self.std_fns.has(&prop.0) {
-								std_fn_call = Some(prop.0.clone());
-							} else {
-								return Err(RuntimeError::PropNotFound(prop.0.clone()));
-							}
+				AccessorComponent::Property(prop)
+				| AccessorComponent::OptionalProperty(prop) => {
+					let field = match &previous_value {
+						Value::Object(obj) => obj.get(&prop.0),
+						Value::Struct(s) => s.values.get(&prop.0),
+						Value::Math => self.math.constants.get(&prop.0),
+						_ => None,
+					};
+					let method = match &previous_value {
+						Value::Struct(s) => self.values.method(&s.kind.0, &prop.0),
+						_ => None,
+					};
+					// fields override methods, which override functions defined in
+					// modules, which override std function names. `math` only has
+					// its own constants and functions.
+					callee = &prop.0;
+					if let Some(p) = field {
+						if let Value::Struct(s) = &previous_value {
+							field_scope = Some(s.scope());
 						}
-					} else {
-						// object props override std function names.
-						if self.std_fns.has(&prop.0) {
-							std_fn_call = Some(prop.0.clone());
-						} else {
-							return Err(RuntimeError::NoPropertyOnKind(
-								previous_value.kind(),
-								prop.0.clone(),
-							));
+						expr = Expression::new(p.clone());
+					} else if let Some(method) = method {
+						pending_call = Some(PendingCall::Method(method));
+					} else if let Value::Math = &previous_value {
+						if !self.math.has(&prop.0) {
+							return Err(RuntimeError::PropNotFound(format!(
+								"math.{}",
+								prop.0
+							)));
 						}
+						pending_call = Some(PendingCall::Math(prop.0.clone()));
+					} else if let Some(f) = self.values.function(&prop.0) {
+						pending_call = Some(PendingCall::Function(prop.0.clone(), f));
+					} else if self.std_fns.has(&prop.0) {
+						pending_call = Some(PendingCall::Std(prop.0.clone()));
+					} else if let Value::Object(_) = &previous_value {
+						return Err(RuntimeError::PropNotFound(prop.0.clone()));
+					} else {
+						return Err(RuntimeError::NoPropertyOnKind(
+							previous_value.kind(),
+							prop.0.clone(),
+						));
 					}
 				}
 				AccessorComponent::Index(i) => {
 					expr =
 						Expression::new(previous_value.index(&self.evaluate(ctx, i)?)?);
 				}
-				AccessorComponent::Call(call) => {
-					if let Some(fn_name) = &std_fn_call {
+				AccessorComponent::Call(call) => match pending_call.take() {
+					Some(PendingCall::Std(fn_name)) => {
 						let std_call_res = self.std_fns.call(
 							&self,
 							ctx,
@@ -186,36 +356,223 @@ impl Runtime {
 							&previous_value,
 						)?;
 						expr = Expression::new(std_call_res);
-						std_fn_call = None;
-					} else {
-						if let Value::Function(FunctionValue {
-							args,
-							expr: fn_expr,
-						}) = &previous_value
-						{
-							let mut new_ctx = RuntimeContext::new();
-							for (var, expr) in args.iter().zip(call.iter()) {
-								new_ctx
-									.params
-									.insert(var.0.clone(), self.valueify(ctx, expr)?);
+					}
+					Some(PendingCall::Method(method)) => {
+						let self_ctx =
+							RuntimeContext::one("self", Expression::new(previous_value));
+						expr = match as_function(&method.value) {
+							Some(f) => self.call_function(callee, ctx, f, call, self_ctx)?,
+							None if !call.is_empty() => {
+								return Err(RuntimeError::WrongArgumentCount(
+									callee.to_string(),
+									0,
+									call.len(),
+								))
 							}
-							expr = self.valueify(&new_ctx, fn_expr)?;
+							None => self.valueify(&self_ctx, &method.value)?,
+						};
+					}
+					Some(PendingCall::Math(fn_name)) => {
+						let args = call
+							.iter()
+							.map(|arg| self.evaluate(ctx, arg))
+							.collect::<Result<Vec<Value>>>()?;
+						expr = Expression::new(self.math.call(&fn_name, &args)?);
+					}
+					Some(PendingCall::Function(_, f)) => {
+						let mut args = vec![Expression::new(previous_value)];
+						for arg in call {
+							args.push(self.valueify(ctx, arg)?);
+						}
+						expr =
+							self.call_with_values(callee, f, args, RuntimeContext::new())?;
+					}
+					None => {
+						if let Value::Function(f) = &previous_value {
+							let fn_ctx = scope.unwrap_or_else(RuntimeContext::new);
+							expr = self.call_function(callee, ctx, f, call, fn_ctx)?;
 						} else {
 							return Err(RuntimeError::CannotCallKind(previous_value.kind()));
 						}
 					}
-				}
+				},
 			}
 		}
-		if let Some(uncalled_std_fn_name) = std_fn_call {
-			return Err(RuntimeError::BadStdFnCall(format!(
-				"{} is a standard function, not a value, and must be called",
-				uncalled_std_fn_name
-			)));
+		if let Some(pending) = pending_call {
+			return Err(pending.uncalled());
 		}
 		Ok(expr.un_nest())
 	}
 
+	/// Creates a variant of an enum, like `Size.Small`, or `Shape.Circle(2)`
+	/// for variants that hold values
+	fn enum_variant(
+		&self,
+		ctx: &RuntimeContext,
+		def: &EnumDefinition,
+		components: &mut std::slice::Iter<AccessorComponent>,
+	) -> Result<Expression<Op, Value>> {
+		let name = &def.name.0;
+		let variant = match components.next() {
+			Some(AccessorComponent::Property(variant)) => {
+				def.variant(&variant.0).ok_or_else(|| {
+					RuntimeError::UnknownVariant(name.to_string(), variant.0.clone())
+				})?
+			}
+			_ => return Err(RuntimeError::VariableNotFound(name.to_string())),
+		};
+		let values = if variant.payload.is_empty() {
+			Vec::new()
+		} else {
+			match components.next() {
+				Some(AccessorComponent::Call(args)) => args
+					.iter()
+					.map(|arg| self.evaluate(ctx, arg))
+					.collect::<Result<Vec<Value>>>()?,
+				_ => {
+					return Err(RuntimeError::WrongPayload(
+						variant.name.0.clone(),
+						variant.payload.len(),
+						0,
+					))
+				}
+			}
+		};
+		Ok(Expression::new(Value::Enum(EnumValue::build(
+			self, def, variant, values,
+		)?)))
+	}
+
+	/// A row of a rolled table, like `wild_magic.roll()`
+	fn roll_table(
+		&self,
+		name: &str,
+		variable: &Variable,
+	) -> Result<Expression<Op, Value>> {
+		let roll = self
+			.values
+			.table(name)
+			.ok_or_else(|| RuntimeError::VariableNotFound(name.to_string()))?;
+		let rows = if self.graph.is_pure(name) {
+			self.cached_value(name, variable)?
+		} else {
+			self.evaluate(&RuntimeContext::new(), &variable.value)?
+		};
+		Ok(Expression::new(table::roll(self, name, roll, rows)?))
+	}
+
+	/// Standard functions can be called with the value as their first argument,
+	/// so `int(x)` is the same as `x.int()`
+	fn free_std_call(
+		&self,
+		ctx: &RuntimeContext,
+		name: &str,
+		component: Option<&AccessorComponent>,
+	) -> Result<Expression<Op, Value>> {
+		if let (Some(native), Some(AccessorComponent::Call(args))) =
+			(self.std_fns.native.get(name), component)
+		{
+			let args = args
+				.iter()
+				.map(|arg| self.evaluate(ctx, arg))
+				.collect::<Result<Vec<Value>>>()?;
+			return Ok(Expression::new(native.call(self, args)?));
+		}
+		match component {
+			Some(AccessorComponent::Call(args))
+				if self.std_fns.has(name) && !args.is_empty() =>
+			{
+				let val = self.evaluate(ctx, &args[0])?;
+				Ok(Expression::new(self.std_fns.call(
+					self,
+					ctx,
+					&args[1..].to_vec(),
+					name,
+					&val,
+				)?))
+			}
+			_ => Err(RuntimeError::VariableNotFound(name.to_string())),
+		}
+	}
+
+	/// Calls a function with arguments from the calling context. Arguments for
+	/// parameters with a declared type are evaluated right away to check them,
+	/// and so is the result if the function declares what it returns.
+	pub fn call_function(
+		&self,
+		name: &str,
+		ctx: &RuntimeContext,
+		f: &FunctionValue,
+		args: &[Expression<Op, ExpressionToken>],
+		fn_ctx: RuntimeContext,
+	) -> Result<Expression<Op, Value>> {
+		let args = args
+			.iter()
+			.map(|arg| self.valueify(ctx, arg))
+			.collect::<Result<Vec<_>>>()?;
+		self.call_with_values(name, f, args, fn_ctx)
+	}
+
+	/// Calls a function with arguments that have already been turned into
+	/// values. `name` is what the function is called in errors.
+	pub fn call_with_values(
+		&self,
+		name: &str,
+		f: &FunctionValue,
+		args: Vec<Expression<Op, Value>>,
+		mut fn_ctx: RuntimeContext,
+	) -> Result<Expression<Op, Value>> {
+		if args.len() != f.args.len() {
+			return Err(RuntimeError::WrongArgumentCount(
+				name.to_string(),
+				f.args.len(),
+				args.len(),
+			));
+		}
+		for ((name, t), arg) in f.args.iter().zip(f.arg_types.iter()).zip(args) {
+			let arg = match t {
+				Some(t) => {
+					let value = self.val_expr_collapse(&RuntimeContext::new(), &arg)?;
+					if !value.is_type(t, &self.values) {
+						return Err(RuntimeError::WrongArgumentType(
+							name.0.clone(),
+							t.clone(),
+							value.kind(),
+						));
+					}
+					Expression::new(value)
+				}
+				None => arg,
+			};
+			fn_ctx.params.insert(name.0.clone(), arg);
+		}
+		let result = self.valueify(&fn_ctx, &f.expr)?;
+		match &f.ret {
+			Some(t) => {
+				let value = self.val_expr_collapse(&fn_ctx, &result)?;
+				if !value.is_type(t, &self.values) {
+					return Err(RuntimeError::WrongReturnType(t.clone(), value.kind()));
+				}
+				Ok(Expression::new(value))
+			}
+			None => Ok(result),
+		}
+	}
+
+	/// Evaluates a variable that never rolls dice only once.
+	fn cached_value(&self, name: &str, variable: &Variable) -> Result<Value> {
+		if let Some(value) = self.values.cache.borrow().get(name) {
+			return Ok(value.clone());
+		}
+		let value = self.evaluate(&RuntimeContext::new(), &variable.value)?;
+		self
+			.values
+			.cache
+			.borrow_mut()
+			.insert(name.to_string(), value.clone());
+		Ok(value)
+	}
+
 	pub fn evaluate(
 		&self,
 		ctx: &RuntimeContext,
@@ -300,6 +657,7 @@ impl Runtime {
 				))),
 				Op::Comparator(c) => Ok(RolledOp::Comparator(c.clone())),
 				Op::Logic(l) => Ok(RolledOp::Logic(l.clone())),
+				Op::Coalesce => Ok(RolledOp::Coalesce),
 				_ => unreachable!(),
 			})
 			.ok_op()?;
@@ -336,20 +694,101 @@ impl Runtime {
 		Ok(self.climber.process(&into_prec(expr), &self, ctx)?)
 	}
 
+	/// Changes the definition of a variable in the source of the module it's
+	/// defined in, keeping the rest of the source as it was, then reloads
+	/// the runtime with the change.
 	pub fn edit_variable(
 		&mut self,
 		var_name: &str,
 		var_value: &str,
 	) -> Result<()> {
-		todo!();
+		let var_value = var_value.trim().trim_end_matches(';').trim_end();
+		tokenize::<Expression<Op, ExpressionToken>>(var_value)?;
+
+		let (source, real_name) = match self.values.defining_module(var_name) {
+			Some((Some(module), real_name)) => (
+				ModuleSource {
+					url: module.using.path.0.clone(),
+					parent: module.parent.clone(),
+				},
+				real_name,
+			),
+			Some((None, real_name)) => (
+				self.root.clone().ok_or(RuntimeError::NoSourceFile)?,
+				real_name,
+			),
+			None => return Err(RuntimeError::VariableNotFound(var_name.to_string())),
+		};
+
+		let mut src =
+			mod_loader::load(&mut self.mod_loader, &source.url, &source.parent)?;
+		let range = find_variable_expression(&src, &real_name)
+			.ok_or_else(|| RuntimeError::CannotEditVariable(var_name.to_string()))?;
+		src.replace_range(range, var_value);
+		tokenize::<Module>(&src)?;
+
+		mod_loader::rewrite(
+			&mut self.mod_loader,
+			&source.url,
+			&source.parent,
+			&src,
+		)?;
+		self.reload_module(&source.url)
 	}
+
+	/// Finds type errors in the loaded module without running anything
+	pub fn check(&self) -> Vec<Diagnostic> {
+		Checker::new(self).check()
+	}
+}
+
+/// A function that can only be called right where it's accessed
+enum PendingCall<'a> {
+	Std(String),
+	Method(&'a Variable),
+	/// A function defined in a module, called on a value like `x.double()`
+	Function(String, &'a FunctionValue),
+	/// A function of the `math` namespace, like `math.sqrt`
+	Math(String),
 }
 
+impl<'a> PendingCall<'a> {
+	fn uncalled(&self) -> RuntimeError {
+		RuntimeError::BadStdFnCall(match self {
+			PendingCall::Std(name) => format!(
+				"{} is a standard function, not a value, and must be called",
+				name
+			),
+			PendingCall::Method(method) => {
+				format!(
+					"{} is a method, not a value, and must be called",
+					method.name
+				)
+			}
+			PendingCall::Function(name, _) => {
+				format!("{} is called on a value here, so it must be called", name)
+			}
+			PendingCall::Math(name) => format!(
+				"math.{} is a function, not a value, and must be called",
+				name
+			),
+		})
+	}
+}
+
+#[derive(Clone)]
 pub struct RuntimeValues {
 	// TODO: look into making these into radix trees instead
 	pub structs: BTreeMap<String, StructDefinition>,
+	pub enums: BTreeMap<String, EnumDefinition>,
+	/// Tables that can be rolled on, like `table wild_magic d100`
+	pub tables: BTreeMap<String, TableRoll>,
+	/// Struct name -> method name -> method
+	pub methods: BTreeMap<String, BTreeMap<String, Variable>>,
 	pub variables: BTreeMap<String, Variable>,
 	pub loaded_modules: Vec<LoadedModule>,
+	/// Evaluated values of variables that never roll dice
+	pub cache: RefCell<HashMap<String, Value>>,
 }
 
 impl RuntimeValues {
@@ -357,8 +796,62 @@ impl RuntimeValues {
 		module: Module,
 		path: &str,
 		loader: &mut Box<dyn ModLoader>,
+	) -> Result<Self> {
+		Self::build(module, path, loader, Vec::new())
+	}
+
+	/// Replaces every definition with the ones in a new version of this
+	/// module. Modules loaded by use statements that haven't changed are kept
+	/// as they are, instead of being loaded again.
+	pub fn reload(
+		&mut self,
+		module: Module,
+		path: &str,
+		loader: &mut Box<dyn ModLoader>,
+	) -> Result<()> {
+		let mut previous = std::mem::take(&mut self.loaded_modules);
+		let cache = std::mem::take(&mut self.cache);
+		// the prelude isn't loaded by a use statement, so it's kept as it is
+		let prelude = previous
+			.iter()
+			.position(LoadedModule::is_prelude)
+			.map(|i| previous.remove(i));
+		*self = Self::build(module, path, loader, previous)?;
+		self.loaded_modules.splice(0..0, prelude);
+		self.cache = cache;
+		Ok(())
+	}
+
+	/// Reloads every module loaded from a url, no matter how deeply nested it
+	/// is. Returns false if no module was loaded from that url.
+	pub fn reload_modules(
+		&mut self,
+		url: &str,
+		loader: &mut Box<dyn ModLoader>,
+	) -> Result<bool> {
+		let mut found = false;
+		for module in self.loaded_modules.iter_mut() {
+			if module.using.path.0 == url {
+				module.reload(loader)?;
+				found = true;
+			} else if module.values.reload_modules(url, loader)? {
+				module.qualify_hidden();
+				found = true;
+			}
+		}
+		Ok(found)
+	}
+
+	fn build(
+		module: Module,
+		path: &str,
+		loader: &mut Box<dyn ModLoader>,
+		mut previous: Vec<LoadedModule>,
 	) -> Result<Self> {
 		let mut structs = BTreeMap::new();
+		let mut enums = BTreeMap::new();
+		let mut tables = BTreeMap::new();
+		let mut methods = BTreeMap::<String, BTreeMap<String, Variable>>::new();
 		let mut variables = BTreeMap::new();
 		let mut loaded_modules = Vec::new();
 
@@ -370,17 +863,49 @@ impl RuntimeValues {
 				ivory_tokenizer::commands::Command::StructDefinition(d) => {
 					structs.insert(d.name.0.clone(), d);
 				}
+				ivory_tokenizer::commands::Command::EnumDefinition(d) => {
+					enums.insert(d.name.0.clone(), d);
+				}
+				ivory_tokenizer::commands::Command::Table(t) => {
+					if let Some(roll) = &t.roll {
+						table::check_ranges(&t.name.0, roll)?;
+						tables.insert(t.name.0.clone(), roll.clone());
+					}
+					let variable = t.into_variable();
+					variables.insert(variable.name.0.clone(), variable);
+				}
+				ivory_tokenizer::commands::Command::StructImpl(i) => {
+					let struct_methods = methods.entry(i.name.0).or_default();
+					for method in i.methods {
+						struct_methods.insert(method.name.0.clone(), method);
+					}
+				}
 				ivory_tokenizer::commands::Command::Use(u) => {
-					loaded_modules.push(LoadedModule::new(loader, &u, path)?);
+					match previous
+						.iter()
+						.position(|m| m.using == u && m.parent == path)
+					{
+						Some(i) => loaded_modules.push(previous.remove(i)),
+						None => loaded_modules.push(LoadedModule::new(loader, &u, path)?),
+					}
 				}
 			}
 		}
-		Ok(RuntimeValues {
+		let values = RuntimeValues {
 			structs,
+			enums,
+			tables,
+			methods,
 			variables,
 			loaded_modules,
-		})
+			cache: RefCell::new(HashMap::new()),
+		};
+		for name in values.structs.keys() {
+			values.struct_fields(name)?;
+		}
+		Ok(values)
 	}
+
 	pub fn variable_names(&self) -> HashSet<String> {
 		let mut vars: HashSet<String> = HashSet::new();
 		for module in self.loaded_modules.iter() {
@@ -389,6 +914,89 @@ impl RuntimeValues {
 		vars.extend(self.variables.keys().cloned().collect::<HashSet<String>>());
 		vars
 	}
+	/// Finds the module a variable is defined in, along with the name it has
+	/// there. `None` means the variable is defined in this module.
+	pub fn defining_module(
+		&self,
+		name: &str,
+	) -> Option<(Option<&LoadedModule>, String)> {
+		if self.variables.contains_key(name) {
+			return Some((None, name.to_string()));
+		}
+		for module in self.loaded_modules.iter().rev() {
+			if let Some(real_name) = module.real_name(name) {
+				if let Some((inner, real_name)) =
+					module.values.defining_module(&real_name)
+				{
+					return Some((inner.or(Some(module)), real_name));
+				}
+			}
+		}
+		None
+	}
+	/// A method of a struct, or of the nearest struct it extends that has one
+	pub fn method(&self, struct_name: &str, name: &str) -> Option<&Variable> {
+		self
+			.lineage(struct_name)
+			.into_iter()
+			.find_map(|s| self.methods.get(s)?.get(name))
+	}
+	/// Every field of a struct, including the ones it inherits. Fields defined
+	/// by a struct replace inherited fields with the same name.
+	pub fn struct_fields(
+		&self,
+		name: &str,
+	) -> Result<Vec<(VariableName, StructDefinitionValue)>> {
+		let mut chain: Vec<String> = Vec::new();
+		let mut current = Some(name);
+		while let Some(struct_name) = current {
+			if chain.iter().any(|c| c == struct_name) {
+				chain.push(struct_name.to_string());
+				return Err(RuntimeError::InheritanceCycle(chain));
+			}
+			let def = self
+				.structs
+				.get(struct_name)
+				.ok_or_else(|| RuntimeError::StructNotFound(struct_name.to_string()))?;
+			chain.push(struct_name.to_string());
+			current = def.parent.as_ref().map(|p| p.0.as_str());
+		}
+		let mut fields: Vec<(VariableName, StructDefinitionValue)> = Vec::new();
+		for struct_name in chain.iter().rev() {
+			for (field, value) in &self.structs[struct_name].values {
+				match fields.iter_mut().find(|(f, _)| f == field) {
+					Some(inherited) => inherited.1 = value.clone(),
+					None => fields.push((field.clone(), value.clone())),
+				}
+			}
+		}
+		Ok(fields)
+	}
+	/// Whether a struct is `parent`, or extends it
+	pub fn is_subtype(&self, struct_name: &str, parent: &str) -> bool {
+		self.lineage(struct_name).contains(&parent)
+	}
+	/// A struct's name, followed by every struct it extends
+	fn lineage<'a>(&'a self, mut struct_name: &'a str) -> Vec<&'a str> {
+		let mut lineage = vec![struct_name];
+		while let Some(parent) = self
+			.structs
+			.get(struct_name)
+			.and_then(|def| def.parent.as_ref())
+		{
+			if lineage.contains(&parent.0.as_str()) {
+				break;
+			}
+			struct_name = parent.0.as_str();
+			lineage.push(struct_name);
+		}
+		lineage
+	}
+	/// A variable that's defined as a function literal, which can be called on
+	/// a value like `x.name()`
+	pub fn function(&self, name: &str) -> Option<&FunctionValue> {
+		as_function(&self.get_variable(name)?.value)
+	}
 	pub fn get_variable(&self, name: &str) -> Option<&Variable> {
 		if let Some(variable) = self.variables.get(name) {
 			return Some(variable);
@@ -398,7 +1006,38 @@ impl RuntimeValues {
 				return Some(variable);
 			}
 		}
-		None
+		// variables a module didn't export are qualified with its path, like
+		// `./lib.iv:helper`, when the ones it did export use them
+		let (path, name) = name.rsplit_once(':')?;
+		self.module(path)?.values.get_variable(name)
+	}
+	/// A module loaded by this one, or by any module it uses
+	pub fn module(&self, path: &str) -> Option<&LoadedModule> {
+		self.loaded_modules.iter().rev().find_map(|m| {
+			if m.using.path.0 == path {
+				Some(m)
+			} else {
+				m.values.module(path)
+			}
+		})
+	}
+	/// An enum defined in this module, or in one it uses.
+	pub fn enum_definition(&self, name: &str) -> Option<&EnumDefinition> {
+		if let Some(def) = self.enums.get(name) {
+			return Some(def);
+		}
+		self
+			.loaded_modules
+			.iter()
+			.rev()
+			.find_map(|m| m.enum_definition(name))
+	}
+	/// How to roll on a table, found the same way as the table's variable.
+	pub fn table(&self, name: &str) -> Option<&TableRoll> {
+		if let Some(roll) = self.tables.get(name) {
+			return Some(roll);
+		}
+		self.loaded_modules.iter().rev().find_map(|m| m.table(name))
 	}
 }
 
@@ -420,11 +1059,25 @@ impl RuntimeContext {
 	}
 }
 
+/// Checks that each expression evaluates to the value next to it, of the
+/// same kind
+#[cfg(test)]
+pub(crate) fn assert_cases(runtime: &Runtime, cases: &[(&str, Value)]) {
+	for (expr, val) in cases {
+		let res = runtime
+			.run_val(expr)
+			.unwrap_or_else(|err| panic!("{}: {}", expr, err));
+		assert_eq!(&res, val, "{}", expr);
+		assert_eq!(res.kind(), val.kind(), "{}", expr);
+	}
+}
+
 #[cfg(test)]
 mod test {
 
 	use super::*;
-	use ivory_tokenizer::Parse;
+	use crate::value::ValueKind;
+	use ivory_tokenizer::{itype::Type, Parse};
 
 	fn test_runtime() -> (Runtime, RuntimeContext) {
 		(Runtime::new(rand::thread_rng(), ()), RuntimeContext::new())
@@ -503,6 +1156,87 @@ mod test {
 		runtime.run("d(3)").unwrap();
 	}
 
+	#[test]
+	fn cache_pure_variables() {
+		let mut runtime = Runtime::new(rand::thread_rng(), ());
+		runtime
+			.load(
+				r#"
+		str = 15 + 1;
+		ability_mod = ability -> (ability - 10) /_ 2;
+		str_mod = ability_mod(str);
+		ability_check = ability -> 1d20 + ability_mod(ability);
+		str_check = ability_check(str);
+		"#,
+				"",
+			)
+			.unwrap();
+		assert!(runtime.graph.is_pure("str_mod"));
+		assert!(!runtime.graph.is_pure("ability_check"));
+		assert!(!runtime.graph.is_pure("str_check"));
+
+		assert_eq!(runtime.run_val("str_mod + 1").unwrap(), Value::Integer(4));
+		// the cached value shows up in place of the expression
+		assert!(matches!(
+			runtime.run("str_mod + 1").unwrap().first,
+			ExpressionComponent::Token(Value::Integer(3))
+		));
+		runtime.run("str_check").unwrap();
+		let cache = runtime.values.cache.borrow();
+		assert_eq!(cache.get("str"), Some(&Value::Integer(16)));
+		assert!(matches!(cache.get("str_mod"), Some(Value::Integer(3))));
+		assert!(cache.get("str_check").is_none());
+	}
+
+	#[test]
+	fn invalidate_shared_dependents() {
+		let mut runtime = Runtime::new(rand::thread_rng(), ());
+		let src: String = (2..28)
+			.map(|i| format!("v{} = v{} + v{};", i, i - 1, i - 2))
+			.collect();
+		runtime
+			.load(&format!("v0 = 1; v1 = 1; {}", src), "")
+			.unwrap();
+		assert_eq!(runtime.run_val("v27").unwrap(), Value::Integer(317_811));
+		runtime.update_variable("v0", "2").unwrap();
+		assert!(runtime.values.cache.borrow().get("v27").is_none());
+		assert_eq!(
+			runtime.values.cache.borrow().get("v1"),
+			Some(&Value::Integer(1))
+		);
+		assert_eq!(runtime.run_val("v27").unwrap(), Value::Integer(439_204));
+	}
+
+	#[test]
+	fn rounded_division() {
+		let runtime = Runtime::new(rand::thread_rng(), ());
+		let cases = [
+			("7 /_ 2", 3),
+			("(0 - 7) /_ 2", -4),
+			("7 /^ 2", 4),
+			("4 /^ 2", 2),
+			("(0 - 7) /^ 2", -3),
+			("7 /~ 2", 4),
+			("5 /~ 3", 2),
+			("(0 - 7) /~ 2", -4),
+			("(15 - 10) /_ 2", 2),
+			("(9 - 10) /_ 2", -1),
+		];
+		for (expr, expected) in cases {
+			match runtime.run_val(expr).unwrap() {
+				Value::Integer(i) => assert_eq!(i, expected, "{}", expr),
+				other => panic!("{} should be an integer, got {:?}", expr, other),
+			}
+		}
+		// without rounding, or with decimals, division gives a decimal
+		assert!(
+			matches!(runtime.run_val("7 / 2"), Ok(Value::Decimal(d)) if d == 3.5)
+		);
+		assert!(
+			matches!(runtime.run_val("7.0 /_ 2"), Ok(Value::Decimal(d)) if d == 3.0)
+		);
+	}
+
 	#[test]
 	fn std_functions() {
 		let mut runtime = Runtime::new(rand::thread_rng(), ());
@@ -520,4 +1254,258 @@ mod test {
 		assert!(runtime.run("x.index_of").is_err());
 		assert!(runtime.run("x.index_of[200]").is_err());
 	}
+
+	#[test]
+	fn typed_functions() {
+		let mut runtime = Runtime::new(rand::thread_rng(), ());
+		runtime
+			.load(
+				r#"
+			double = (score: int): int -> score * 2;
+			halve = (score: int): int -> score / 2;
+			apply = (f: (int) -> int, x: int): int -> f(x);
+			untyped = a -> a;
+			"#,
+				"",
+			)
+			.unwrap();
+		assert_eq!(runtime.run_val("double(4)").unwrap(), Value::Integer(8));
+		assert!(matches!(
+			runtime.run("double(\"a\")"),
+			Err(RuntimeError::WrongArgumentType(name, Type::Integer, ValueKind::String))
+				if name == "score"
+		));
+		assert!(matches!(
+			runtime.run("halve(4)"),
+			Err(RuntimeError::WrongReturnType(
+				Type::Integer,
+				ValueKind::Decimal
+			))
+		));
+		assert_eq!(
+			runtime.run_val("apply(double, 3)").unwrap(),
+			Value::Integer(6)
+		);
+		assert_eq!(
+			runtime.run_val("apply(untyped, 3)").unwrap(),
+			Value::Integer(3)
+		);
+		assert!(runtime.run("apply(halve, 3)").is_err());
+		assert!(runtime.run("apply(5, 3)").is_err());
+		assert_eq!(
+			runtime.run_val("[1, 2].map((x: int) -> x + 1)").unwrap(),
+			Value::Array(vec![Value::Integer(2), Value::Integer(3)])
+		);
+		assert!(runtime.run("[\"a\"].map((x: int) -> x)").is_err());
+	}
+
+	#[test]
+	fn optional_values() {
+		let mut runtime = Runtime::new(rand::thread_rng(), ());
+		runtime
+			.load(
+				r#"
+			struct Shield {
+				ac: int
+			}
+
+			struct Character {
+				name: string,
+				shield: Shield?
+			}
+
+			fighter = Character { name: "Bors", shield: Shield { ac: 2 } };
+			wizard = Character { name: "Tim" };
+			ac = c -> 10 + (c.shield?.ac ?? 0);
+			"#,
+				"",
+			)
+			.unwrap();
+		assert_eq!(runtime.run_val("wizard.shield").unwrap(), Value::None);
+		assert_eq!(runtime.run_val("wizard.shield?.ac").unwrap(), Value::None);
+		assert_eq!(runtime.run_val("ac(fighter)").unwrap(), Value::Integer(12));
+		assert_eq!(runtime.run_val("ac(wizard)").unwrap(), Value::Integer(10));
+		assert!(runtime.run_val("wizard.shield.ac").is_err());
+		assert_eq!(runtime.run_val("none ?? 1 + 2").unwrap(), Value::Integer(3));
+		assert_eq!(runtime.run_val("5 ?? 1").unwrap(), Value::Integer(5));
+		assert_eq!(
+			runtime.run_val("none == none").unwrap(),
+			Value::Boolean(true)
+		);
+		assert_eq!(runtime.run_val("none ? 1 : 2").unwrap(), Value::Integer(2));
+		assert!(runtime.run_val("none + 1").is_err());
+		assert!(runtime
+			.run_val("Character { name: \"Al\", shield: 3 }")
+			.is_err());
+	}
+
+	#[test]
+	fn enums() {
+		let mut runtime = Runtime::new(rand::thread_rng(), ());
+		runtime
+			.load(
+				r#"
+			enum DamageType { Fire, Cold, Slashing }
+			enum Shape { Circle(decimal), Rect(int, int), Dot }
+
+			struct Weapon {
+				name: string,
+				damage_type: DamageType
+			}
+
+			sword = Weapon { name: "Sword", damage_type: DamageType.Slashing };
+			resist = t -> match t { Fire => 0, Cold => 1, _ => 2 };
+			area = s -> match s {
+				Circle(r) => r * r * 3,
+				Rect(w, h) => w * h,
+				Dot => 0,
+			};
+			"#,
+				"",
+			)
+			.unwrap();
+		assert_eq!(
+			format!("{}", runtime.run_val("sword.damage_type").unwrap()),
+			"DamageType.Slashing"
+		);
+		assert_eq!(
+			runtime.run_val("resist(sword.damage_type)").unwrap(),
+			Value::Integer(2)
+		);
+		assert_eq!(
+			runtime.run_val("resist(DamageType.Fire)").unwrap(),
+			Value::Integer(0)
+		);
+		assert_eq!(
+			runtime.run_val("area(Shape.Rect(2, 3))").unwrap(),
+			Value::Integer(6)
+		);
+		assert_eq!(
+			runtime.run_val("area(Shape.Circle(2))").unwrap(),
+			Value::Decimal(12.0)
+		);
+		assert_eq!(
+			runtime
+				.run_val("DamageType.Fire == DamageType.Fire")
+				.unwrap(),
+			Value::Boolean(true)
+		);
+		assert!(matches!(
+			runtime.run_val("DamageType.Fier"),
+			Err(RuntimeError::UnknownVariant(..))
+		));
+		assert!(matches!(
+			runtime.run_val("Shape.Rect(2)"),
+			Err(RuntimeError::WrongPayload(..))
+		));
+		assert!(matches!(
+			runtime.run_val("Shape.Circle(\"big\")"),
+			Err(RuntimeError::WrongPayloadType(..))
+		));
+		assert!(matches!(
+			runtime.run_val("match DamageType.Cold { Fire => 1, Cold => 2 }"),
+			Err(RuntimeError::NonExhaustiveMatch(_, missing)) if missing == vec!["Slashing"]
+		));
+		assert!(matches!(
+			runtime.run_val("match 3 { Fire => 1, _ => 2 }"),
+			Err(RuntimeError::CannotMatchKind(ValueKind::Integer))
+		));
+		assert!(runtime
+			.run_val("Weapon { name: \"Axe\", damage_type: 3 }")
+			.is_err());
+	}
+
+	#[test]
+	fn argument_counts() {
+		let mut runtime = Runtime::new(rand::thread_rng(), ());
+		runtime
+			.load(
+				r#"
+			double = x -> x * 2;
+			struct Spell { level: int }
+			impl Spell {
+				dc = 8 + self.level;
+			}
+			spell = Spell { level: 3 };
+			"#,
+				"",
+			)
+			.unwrap();
+		let count = |src: &str| match runtime.run(src) {
+			Err(RuntimeError::WrongArgumentCount(name, expected, got)) => {
+				(name, expected, got)
+			}
+			other => panic!("Expected an argument count error, got {:?}", other),
+		};
+		assert_eq!(count("double(1, 2)"), ("double".to_string(), 1, 2));
+		assert_eq!(count("double()"), ("double".to_string(), 1, 0));
+		assert_eq!(count("spell.level.double(1)"), ("double".to_string(), 1, 2));
+		assert_eq!(count("spell.dc(1)"), ("dc".to_string(), 0, 1));
+		assert_eq!(runtime.run_val("spell.dc()").unwrap(), Value::Integer(11));
+		// functions passed to std functions can leave out arguments
+		assert_eq!(
+			runtime.run_val("[1, 2].map(double)").unwrap(),
+			Value::Array(vec![Value::Integer(2), Value::Integer(4)])
+		);
+	}
+
+	#[test]
+	fn function_call_syntax() {
+		let mut runtime = Runtime::new(rand::thread_rng(), ());
+		runtime
+			.load(
+				r#"
+			double = v -> v * 2;
+			add = a b -> a + b;
+			len = v -> 100;
+			bump = v -> v + 1d4;
+			x = 4;
+			stats = { double: 5 };
+			doubled = x.double();
+			bumped = x.bump();
+			"#,
+				"",
+			)
+			.unwrap();
+		assert_cases(
+			&runtime,
+			&[
+				("x.double()", Value::Integer(8)),
+				("x.double().double()", Value::Integer(16)),
+				("[1, 2].sum().double()", Value::Integer(6)),
+				("x.add(3)", Value::Integer(7)),
+				// functions defined in modules override standard functions
+				("[1, 2].len()", Value::Integer(100)),
+				// but not fields
+				("stats.double", Value::Integer(5)),
+				("doubled", Value::Integer(8)),
+			],
+		);
+		assert!(runtime.run_val("x.double").is_err());
+		assert!(runtime.run_val("x.triple()").is_err());
+		assert!(runtime.graph.dependencies_of("doubled").contains("double"));
+		assert!(runtime.graph.is_pure("doubled"));
+		assert!(!runtime.graph.is_pure("bumped"));
+	}
+
+	#[test]
+	fn user_functions_replace_std() {
+		let mut runtime = Runtime::new(rand::thread_rng(), ());
+		runtime
+			.load("len = v -> \"long\"; name = \"Ivory\"; n = name.len();", "")
+			.unwrap();
+		assert_cases(
+			&runtime,
+			&[
+				("[1, 2].len()", Value::String("long".to_string())),
+				("name.len()", Value::String("long".to_string())),
+				("len(name)", Value::String("long".to_string())),
+				// other standard functions are still there
+				("[1, 2].sum()", Value::Integer(3)),
+			],
+		);
+		// the checker picks the same function
+		let checker = Checker::new(&runtime);
+		assert_eq!(checker.variable("n"), Type::String);
+	}
 }
@@ -1,14 +1,17 @@
-use std::collections::HashMap;
+use std::{cmp::Ordering, collections::HashMap};
 
 use ivory_expression::Expression;
 use ivory_tokenizer::{
-	expression::{ExpressionToken, Op},
+	expression::{logic::Comparator, math::ExprOpMathKind, ExpressionToken, Op},
+	itype::Type,
 	values::function::FunctionValue,
 };
 
 use crate::{
+	expr::RolledOp,
+	roll::{Draw, Roll},
 	runtime::{Runtime, RuntimeContext},
-	value::Value,
+	value::{Value, ValueKind},
 	Result, RuntimeError,
 };
 
@@ -19,20 +22,192 @@ type StdFn = fn(
 	val: &Value,
 ) -> Result<Value>;
 
+type NativeFnBody = Box<dyn Fn(&[Value]) -> Result<Value>>;
+
+/// A function added by an application embedding the runtime. Its arguments
+/// are evaluated and checked against its parameter types before it's called.
+/// When it's called on a value, like `x.name()`, that value is the first
+/// argument.
+pub struct NativeFn {
+	pub params: Vec<Type>,
+	pub ret: Type,
+	/// Pure functions always return the same thing for the same arguments, so
+	/// variables that use them can be cached
+	pub pure: bool,
+	body: NativeFnBody,
+}
+
+impl NativeFn {
+	pub fn new<F>(params: Vec<Type>, ret: Type, body: F) -> Self
+	where
+		F: Fn(&[Value]) -> Result<Value> + 'static,
+	{
+		Self {
+			params,
+			ret,
+			pure: false,
+			body: Box::new(body),
+		}
+	}
+
+	/// Marks the function as always returning the same thing for the same
+	/// arguments
+	pub fn pure(mut self) -> Self {
+		self.pure = true;
+		self
+	}
+
+	pub fn call(&self, runtime: &Runtime, args: Vec<Value>) -> Result<Value> {
+		if args.len() != self.params.len() {
+			return Err(RuntimeError::BadStdFnCall(format!(
+				"function requires {} arguments, got {}",
+				self.params.len(),
+				args.len()
+			)));
+		}
+		for (i, (t, arg)) in self.params.iter().zip(args.iter()).enumerate() {
+			if !arg.is_type(t, &runtime.values) {
+				return Err(RuntimeError::WrongArgumentType(
+					(i + 1).to_string(),
+					t.clone(),
+					arg.kind(),
+				));
+			}
+		}
+		let res = (self.body)(&args)?;
+		if !res.is_type(&self.ret, &runtime.values) {
+			return Err(RuntimeError::WrongReturnType(self.ret.clone(), res.kind()));
+		}
+		Ok(res)
+	}
+}
+
+/// A standard function, along with what the checker knows about it
+pub struct StdFnDef {
+	body: StdFn,
+	/// What the function returns, `any` when it depends on the arguments
+	pub ret: Type,
+	/// The function chooses at random, so variables that call it are never
+	/// cached
+	pub random: bool,
+}
+
+impl StdFnDef {
+	fn new(body: StdFn, ret: Type) -> Self {
+		Self {
+			body,
+			ret,
+			random: false,
+		}
+	}
+
+	fn random(mut self) -> Self {
+		self.random = true;
+		self
+	}
+}
+
 pub struct StdFnLibrary {
-	pub fns: HashMap<String, StdFn>,
+	pub fns: HashMap<String, StdFnDef>,
+	/// Functions added with `Runtime::register_fn`, which replace standard
+	/// functions with the same name
+	pub native: HashMap<String, NativeFn>,
 }
 
 impl StdFnLibrary {
 	pub fn new() -> Self {
-		let mut fns = HashMap::<String, StdFn>::new();
+		let mut fns = HashMap::<String, StdFnDef>::new();
 
-		fns.insert("index_of".to_string(), index_of);
-		fns.insert("len".to_string(), len);
-		fns.insert("map".to_string(), map);
-		fns.insert("fold".to_string(), fold);
+		fns.insert(
+			"index_of".to_string(),
+			StdFnDef::new(index_of, optional(Type::Integer)),
+		);
+		fns.insert("len".to_string(), StdFnDef::new(len, Type::Integer));
+		fns.insert("get".to_string(), StdFnDef::new(get, optional(Type::Any)));
+		fns.insert("map".to_string(), StdFnDef::new(map, Type::Any));
+		fns.insert("fold".to_string(), StdFnDef::new(fold, Type::Any));
+		fns.insert("filter".to_string(), StdFnDef::new(filter, Type::Any));
+		fns.insert("sum".to_string(), StdFnDef::new(sum, Type::Any));
+		fns.insert("product".to_string(), StdFnDef::new(product, Type::Any));
+		fns.insert("min".to_string(), StdFnDef::new(min, optional(Type::Any)));
+		fns.insert("max".to_string(), StdFnDef::new(max, optional(Type::Any)));
+		fns.insert("sort".to_string(), StdFnDef::new(sort, Type::Any));
+		fns.insert("sort_by".to_string(), StdFnDef::new(sort_by, Type::Any));
+		fns.insert("reverse".to_string(), StdFnDef::new(reverse, Type::Any));
+		fns.insert("slice".to_string(), StdFnDef::new(slice, Type::Any));
+		fns.insert(
+			"contains".to_string(),
+			StdFnDef::new(contains, Type::Boolean),
+		);
+		fns.insert("any".to_string(), StdFnDef::new(any, Type::Boolean));
+		fns.insert("all".to_string(), StdFnDef::new(all, Type::Boolean));
+		fns.insert("find".to_string(), StdFnDef::new(find, optional(Type::Any)));
+		fns.insert("flatten".to_string(), StdFnDef::new(flatten, Type::Any));
+		fns.insert("zip".to_string(), StdFnDef::new(zip, Type::Any));
+		fns.insert("enumerate".to_string(), StdFnDef::new(enumerate, Type::Any));
+		fns.insert("unique".to_string(), StdFnDef::new(unique, Type::Any));
+		fns.insert("count".to_string(), StdFnDef::new(count, Type::Integer));
+		fns.insert("pick".to_string(), StdFnDef::new(pick, Type::Any).random());
+		fns.insert(
+			"shuffle".to_string(),
+			StdFnDef::new(shuffle, Type::Any).random(),
+		);
+		fns.insert(
+			"weighted_pick".to_string(),
+			StdFnDef::new(weighted_pick, Type::Any).random(),
+		);
+		fns.insert("upper".to_string(), StdFnDef::new(upper, Type::String));
+		fns.insert("lower".to_string(), StdFnDef::new(lower, Type::String));
+		fns.insert("trim".to_string(), StdFnDef::new(trim, Type::String));
+		fns.insert(
+			"split".to_string(),
+			StdFnDef::new(split, Type::Array(Box::new(Type::String))),
+		);
+		fns.insert("join".to_string(), StdFnDef::new(join, Type::String));
+		fns.insert("replace".to_string(), StdFnDef::new(replace, Type::String));
+		fns.insert(
+			"starts_with".to_string(),
+			StdFnDef::new(starts_with, Type::Boolean),
+		);
+		fns.insert(
+			"ends_with".to_string(),
+			StdFnDef::new(ends_with, Type::Boolean),
+		);
+		fns.insert("repeat".to_string(), StdFnDef::new(repeat, Type::String));
+		fns.insert(
+			"pad_left".to_string(),
+			StdFnDef::new(pad_left, Type::String),
+		);
+		fns.insert(
+			"pad_right".to_string(),
+			StdFnDef::new(pad_right, Type::String),
+		);
+		fns.insert(
+			"chars".to_string(),
+			StdFnDef::new(chars, Type::Array(Box::new(Type::String))),
+		);
+		fns.insert(
+			"keys".to_string(),
+			StdFnDef::new(keys, Type::Array(Box::new(Type::String))),
+		);
+		fns.insert("values".to_string(), StdFnDef::new(values, Type::Any));
+		fns.insert("entries".to_string(), StdFnDef::new(entries, Type::Any));
+		fns.insert(
+			"from_entries".to_string(),
+			StdFnDef::new(from_entries, Type::Object),
+		);
+		fns.insert("has".to_string(), StdFnDef::new(has, Type::Boolean));
+		fns.insert("merge".to_string(), StdFnDef::new(merge, Type::Object));
+		fns.insert("without".to_string(), StdFnDef::new(without, Type::Object));
+		fns.insert("int".to_string(), StdFnDef::new(int, Type::Integer));
+		fns.insert("decimal".to_string(), StdFnDef::new(decimal, Type::Decimal));
+		fns.insert("string".to_string(), StdFnDef::new(string, Type::String));
+		fns.insert("bool".to_string(), StdFnDef::new(bool, Type::Boolean));
 
-		Self { fns }
+		Self {
+			fns,
+			native: HashMap::new(),
+		}
 	}
 
 	pub fn call(
@@ -43,26 +218,44 @@ impl StdFnLibrary {
 		name: &str,
 		val: &Value,
 	) -> Result<Value> {
-		if let Some(f) = self.fns.get(name) {
-			f(runtime, ctx, args, val).map_err(|err| match err {
-				RuntimeError::BadStdFnCall(info) => RuntimeError::BadStdFnCall(
-					format!("Error calling {}: {}", name, info),
-				),
-				other => other,
-			})
+		let res = if let Some(native) = self.native.get(name) {
+			let mut values = vec![val.clone()];
+			for arg in args {
+				values.push(runtime.evaluate(ctx, arg)?);
+			}
+			native.call(runtime, values)
+		} else if let Some(f) = self.fns.get(name) {
+			(f.body)(runtime, ctx, args, val)
 		} else {
-			Err(RuntimeError::BadStdFnCall(format!(
+			return Err(RuntimeError::BadStdFnCall(format!(
 				"Function {} not found for kind {}",
 				name,
 				val.kind()
-			)))
-		}
+			)));
+		};
+		res.map_err(|err| match err {
+			RuntimeError::BadStdFnCall(info) => {
+				RuntimeError::BadStdFnCall(format!("Error calling {}: {}", name, info))
+			}
+			other => other,
+		})
 	}
 	pub fn has(&self, name: &str) -> bool {
-		self.fns.contains_key(name)
+		self.fns.contains_key(name) || self.native.contains_key(name)
+	}
+	/// Whether a function always returns the same thing for the same arguments
+	pub fn is_pure(&self, name: &str) -> bool {
+		match self.native.get(name) {
+			Some(native) => native.pure,
+			None => !self.fns.get(name).is_some_and(|f| f.random),
+		}
 	}
 }
 
+fn optional(t: Type) -> Type {
+	Type::Optional(Box::new(t))
+}
+
 fn no_fn_err(name: &str, val: &Value) -> RuntimeError {
 	RuntimeError::NoStdFnForKind(name.to_string(), val.kind())
 }
@@ -97,8 +290,270 @@ fn enforce_len(
 	}
 }
 
+/// For functions with arguments that can be left out
+fn enforce_len_between(
+	args: &[Expression<Op, ExpressionToken>],
+	min: usize,
+	max: usize,
+) -> Result<()> {
+	if (min..=max).contains(&args.len()) {
+		Ok(())
+	} else {
+		Err(RuntimeError::BadStdFnCall(format!(
+			"std function requires {} to {} arguments",
+			min, max
+		)))
+	}
+}
+
+fn cast_err(val: &Value, to: ValueKind) -> RuntimeError {
+	RuntimeError::CannotCast(val.kind(), to)
+}
+
+/// Calls a function passed to a standard function. Extra arguments are
+/// ignored, so functions can leave out ones they don't need.
+fn call(
+	runtime: &Runtime,
+	f: &FunctionValue,
+	mut args: Vec<Expression<Op, Value>>,
+) -> Result<Value> {
+	args.truncate(f.args.len());
+	let res =
+		runtime.call_with_values("function", f, args, RuntimeContext::new())?;
+	runtime.val_expr_collapse(&RuntimeContext::new(), &res)
+}
+
+/// The text of a string a standard function is called on
+fn text<'a>(name: &str, val: &'a Value) -> Result<&'a str> {
+	match val {
+		Value::String(s) => Ok(s),
+		_ => Err(no_fn_err(name, val)),
+	}
+}
+
+/// Fills a string with a character up to a number of characters, on the left
+/// or the right
+fn pad(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	name: &str,
+	val: &Value,
+	left: bool,
+) -> Result<Value> {
+	enforce_len_between(args, 1, 2)?;
+	let s = text(name, val)?;
+	let width = get_arg(runtime, ctx, args, 0)?.to_uint()? as usize;
+	let fill = match args.len() {
+		2 => get_arg(runtime, ctx, args, 1)?.to_string()?,
+		_ => " ".to_string(),
+	};
+	let mut fill_chars = fill.chars();
+	let fill = match (fill_chars.next(), fill_chars.next()) {
+		(Some(c), None) => c,
+		_ => {
+			return Err(RuntimeError::BadStdFnCall(format!(
+				".{}() can only fill with a single character",
+				name
+			)))
+		}
+	};
+	let padding = fill
+		.to_string()
+		.repeat(width.saturating_sub(s.chars().count()));
+	Ok(Value::String(if left {
+		padding + s
+	} else {
+		s.to_string() + &padding
+	}))
+}
+
+/// The fields of an object a standard function is called on, sorted by name
+fn object_entries<'a>(
+	name: &str,
+	val: &'a Value,
+) -> Result<Vec<(&'a String, &'a Value)>> {
+	match val {
+		Value::Object(object) => {
+			let mut entries = object.iter().collect::<Vec<_>>();
+			entries.sort_by(|a, b| a.0.cmp(b.0));
+			Ok(entries)
+		}
+		_ => Err(no_fn_err(name, val)),
+	}
+}
+
+/// Fields of `b` replace the ones in `a`. When merging deeply, objects in
+/// both are merged instead of replaced.
+fn merge_objects(
+	a: &HashMap<String, Value>,
+	b: &HashMap<String, Value>,
+	deep: bool,
+) -> HashMap<String, Value> {
+	let mut merged = a.clone();
+	for (k, v) in b {
+		let v = match (merged.get(k), v) {
+			(Some(Value::Object(a)), Value::Object(b)) if deep => {
+				Value::Object(merge_objects(a, b, deep))
+			}
+			_ => v.clone(),
+		};
+		merged.insert(k.clone(), v);
+	}
+	merged
+}
+
+/// The values of an array a standard function is called on
+fn array<'a>(name: &str, val: &'a Value) -> Result<&'a Vec<Value>> {
+	match val {
+		Value::Array(array) => Ok(array),
+		_ => Err(no_fn_err(name, val)),
+	}
+}
+
+/// Takes `count` values out of an array at random, rolling a die with a side
+/// for each value that's left every time
+fn draw(
+	runtime: &Runtime,
+	items: &[Value],
+	count: usize,
+) -> Result<(Vec<Roll>, Vec<Value>)> {
+	if count > items.len() {
+		return Err(RuntimeError::BadStdFnCall(format!(
+			"cannot pick {} values from an array of {}",
+			count,
+			items.len()
+		)));
+	}
+	let mut left = items.to_vec();
+	let mut rolls = Vec::new();
+	let mut picked = Vec::new();
+	for _ in 0..count {
+		let roll = Roll::die(runtime, left.len() as u32);
+		picked.push(left.remove(roll.value() as usize - 1));
+		rolls.push(roll);
+	}
+	Ok((rolls, picked))
+}
+
+/// Records a random choice, so it can be shown along with the result
+fn record(
+	runtime: &Runtime,
+	name: String,
+	rolls: Vec<Roll>,
+	value: Value,
+) -> Value {
+	runtime.draws.borrow_mut().push(Draw {
+		name,
+		rolls,
+		value: value.clone(),
+	});
+	value
+}
+
+/// A function argument, like the one `.filter()` takes
+fn fn_arg(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	name: &str,
+) -> Result<FunctionValue> {
+	match get_arg(runtime, ctx, args, 0)? {
+		Value::Function(f) => Ok(f),
+		_ => Err(RuntimeError::BadStdFnCall(format!(
+			".{}() takes only a function as a parameter",
+			name
+		))),
+	}
+}
+
+/// Calls a function with a value of an array and its index, like `.map()`
+/// does, and checks if the result is true
+fn test(
+	runtime: &Runtime,
+	f: &FunctionValue,
+	i: usize,
+	val: &Value,
+) -> Result<bool> {
+	let args = vec![
+		Expression::new(val.clone()),
+		Expression::new(Value::Integer(i as i32)),
+	];
+	call(runtime, f, args)?.to_boolean()
+}
+
+/// Numbers are compared by value, and strings alphabetically
+fn compare(a: &Value, b: &Value) -> Result<Ordering> {
+	let ordering = match (a, b) {
+		(Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+		(Value::String(_), _) | (_, Value::String(_)) => None,
+		_ => match (a.to_decimal(), b.to_decimal()) {
+			(Ok(a), Ok(b)) => a.partial_cmp(&b),
+			_ => None,
+		},
+	};
+	ordering.ok_or_else(|| {
+		RuntimeError::CannotRunOp(
+			a.kind(),
+			RolledOp::Comparator(Comparator::Lt),
+			b.kind(),
+		)
+	})
+}
+
+/// Sorts values by keys, keeping values with the same key in order
+fn sort_keyed(mut keyed: Vec<(Value, Value)>) -> Result<Value> {
+	let mut err = None;
+	keyed.sort_by(|(a, _), (b, _)| {
+		compare(a, b).unwrap_or_else(|e| {
+			err.get_or_insert(e);
+			Ordering::Equal
+		})
+	});
+	match err {
+		Some(err) => Err(err),
+		None => Ok(Value::Array(keyed.into_iter().map(|(_, v)| v).collect())),
+	}
+}
+
+/// Combines every number in an array with an operator, starting from `initial`
+fn combine(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	name: &str,
+	val: &Value,
+	kind: ExprOpMathKind,
+	initial: i32,
+) -> Result<Value> {
+	let op = RolledOp::Math { kind, round: None };
+	array(name, val)?
+		.iter()
+		.try_fold(Value::Integer(initial), |acc, v| match v {
+			Value::Integer(_) | Value::Decimal(_) | Value::Roll(_) => {
+				acc.run_op(v, &op, runtime, ctx)
+			}
+			_ => Err(RuntimeError::WrongExpectedValue(
+				ValueKind::Decimal,
+				v.kind(),
+			)),
+		})
+}
+
+/// The smallest or biggest value of an array, or none if it's empty
+fn extreme(name: &str, val: &Value, wanted: Ordering) -> Result<Value> {
+	let mut best: Option<&Value> = None;
+	for v in array(name, val)? {
+		best = match best {
+			Some(b) if compare(v, b)? != wanted => Some(b),
+			_ => Some(v),
+		};
+	}
+	Ok(best.cloned().unwrap_or(Value::None))
+}
+
 // ========================================================================== //
 
+/// The index of a value in an array, or none if it isn't in it
 pub fn index_of(
 	runtime: &Runtime,
 	ctx: &RuntimeContext,
@@ -108,17 +563,49 @@ pub fn index_of(
 	enforce_len(args, 1)?;
 	let query = get_arg(runtime, ctx, &args, 0)?;
 	match val {
-		Value::Array(array) => Ok(Value::Integer(
+		Value::Array(array) => Ok(
 			array
 				.iter()
-				.enumerate()
-				.find_map(|(i, val)| if val == &query { Some(i as i32) } else { None })
-				.unwrap_or(-1),
-		)),
+				.position(|val| val == &query)
+				.map_or(Value::None, |i| Value::Integer(i as i32)),
+		),
 		_ => Err(no_fn_err("index_of", val)),
 	}
 }
 
+/// Indexes into a value like `val[i]`, but gives none instead of an error
+/// when there's nothing there, or a default if one is given
+pub fn get(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len_between(args, 1, 2)?;
+	let i = get_arg(runtime, ctx, args, 0)?;
+	let found = match val {
+		Value::Array(_)
+		| Value::Object(_)
+		| Value::Struct(_)
+		| Value::String(_)
+		| Value::Roll(_) => match val.index(&i) {
+			Ok(v) => Ok(v),
+			Err(
+				RuntimeError::IndexOutOfBounds(..)
+				| RuntimeError::PropNotFound(_)
+				| RuntimeError::NoPropertyOnKind(..),
+			) => Ok(Value::None),
+			Err(err) => Err(err),
+		},
+		Value::None => Ok(Value::None),
+		_ => Err(no_fn_err("get", val)),
+	}?;
+	match found {
+		Value::None if args.len() == 2 => get_arg(runtime, ctx, args, 1),
+		found => Ok(found),
+	}
+}
+
 pub fn len(
 	_: &Runtime,
 	_: &RuntimeContext,
@@ -127,7 +614,7 @@ pub fn len(
 ) -> Result<Value> {
 	enforce_len(args, 0)?;
 	Ok(Value::Integer(match val {
-		Value::String(s) => s.len(),
+		Value::String(s) => s.chars().count(),
 		Value::Roll(r) => r.rolls.len(),
 		Value::Array(a) => a.len(),
 		Value::Object(o) => o.len(),
@@ -144,33 +631,22 @@ pub fn map(
 	val: &Value,
 ) -> Result<Value> {
 	enforce_len(args, 1)?;
-	if let Value::Function(FunctionValue {
-		args: fn_args,
-		expr,
-	}) = get_arg(runtime, ctx, &args, 0)?
-	{
-		if fn_args.len() != 1 && fn_args.len() != 2 {
+	if let Value::Function(f) = get_arg(runtime, ctx, &args, 0)? {
+		if f.args.len() != 1 && f.args.len() != 2 {
 			return Err(RuntimeError::BadStdFnCall(
 				".map() function parameter needs 1 or 2 parameters itself".to_string(),
 			));
 		}
-		let has_i = fn_args.len() == 2;
 		match val {
 			// Run map over array
 			Value::Array(array) => {
 				let mut new_array = Vec::<Value>::new();
 				for (i, old_val) in array.iter().enumerate() {
-					let mut map_ctx = RuntimeContext::one(
-						fn_args[0].0.as_str(),
+					let args = vec![
 						Expression::new(old_val.clone()),
-					);
-					if has_i {
-						map_ctx.params.insert(
-							fn_args[1].0.clone(),
-							Expression::new(Value::Integer(i as i32)),
-						);
-					}
-					new_array.push(runtime.evaluate(&map_ctx, &expr)?);
+						Expression::new(Value::Integer(i as i32)),
+					];
+					new_array.push(call(runtime, &f, args)?);
 				}
 				Ok(Value::Array(new_array))
 			}
@@ -178,17 +654,11 @@ pub fn map(
 			Value::Object(object) => {
 				let mut new_map = HashMap::<String, Value>::new();
 				for (k, old_val) in object.iter() {
-					let mut map_ctx = RuntimeContext::one(
-						fn_args[0].0.as_str(),
+					let args = vec![
 						Expression::new(old_val.clone()),
-					);
-					if has_i {
-						map_ctx.params.insert(
-							fn_args[1].0.clone(),
-							Expression::new(Value::String(k.clone())),
-						);
-					}
-					new_map.insert(k.clone(), runtime.evaluate(&map_ctx, &expr)?);
+						Expression::new(Value::String(k.clone())),
+					];
+					new_map.insert(k.clone(), call(runtime, &f, args)?);
 				}
 				Ok(Value::Object(new_map))
 			}
@@ -210,24 +680,16 @@ pub fn fold(
 	enforce_len(args, 2)?;
 	let mut initial = get_arg(runtime, ctx, &args, 0)?;
 	let func = get_arg(runtime, ctx, &args, 1)?;
-	if let Value::Function(FunctionValue {
-		args: fn_args,
-		expr,
-	}) = func
-	{
-		if fn_args.len() != 2 {
+	if let Value::Function(f) = func {
+		if f.args.len() != 2 {
 			return Err(RuntimeError::BadStdFnCall(
 				".fold()'s function parameter must have two parameters".to_string(),
 			));
 		}
 		if let Value::Array(vals) = val {
 			for val in vals {
-				let mut map_ctx =
-					RuntimeContext::one(&fn_args[0].0, Expression::new(initial.clone()));
-				map_ctx
-					.params
-					.insert(fn_args[1].0.clone(), Expression::new(val.clone()));
-				initial = runtime.evaluate(&map_ctx, &expr)?;
+				let args = vec![Expression::new(initial), Expression::new(val.clone())];
+				initial = call(runtime, &f, args)?;
 			}
 			Ok(initial)
 		} else {
@@ -241,81 +703,845 @@ pub fn fold(
 	}
 }
 
-#[cfg(test)]
-mod test {
+/// The values of an array, or the fields of an object, the function returns
+/// true for. Functions are given each field's value and name, like `.map()`.
+pub fn filter(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 1)?;
+	let f = fn_arg(runtime, ctx, args, "filter")?;
+	if let Value::Object(object) = val {
+		let mut filtered = HashMap::new();
+		for (k, v) in object {
+			let args = vec![
+				Expression::new(v.clone()),
+				Expression::new(Value::String(k.clone())),
+			];
+			if call(runtime, &f, args)?.to_boolean()? {
+				filtered.insert(k.clone(), v.clone());
+			}
+		}
+		return Ok(Value::Object(filtered));
+	}
+	let mut filtered = Vec::new();
+	for (i, v) in array("filter", val)?.iter().enumerate() {
+		if test(runtime, &f, i, v)? {
+			filtered.push(v.clone());
+		}
+	}
+	Ok(Value::Array(filtered))
+}
 
-	use super::*;
+/// Adds up an array of numbers, which is 0 if it's empty
+pub fn sum(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 0)?;
+	combine(runtime, ctx, "sum", val, ExprOpMathKind::Add, 0)
+}
 
-	fn test_runtime() -> (Runtime, RuntimeContext) {
-		let mut r = Runtime::new(rand::thread_rng(), ());
-		r.load(
-			r#"
-			x = [100, 200, 300, 400, 500, 600];
-			y = "123456789";
-			z = { foo: 10, bar: 100, baz: 1000, child: ["first", "second"] };
-			i = 45;
-			"#,
-			"",
-		)
-		.unwrap();
-		(r, RuntimeContext::new())
-	}
+/// Multiplies an array of numbers, which is 1 if it's empty
+pub fn product(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 0)?;
+	combine(runtime, ctx, "product", val, ExprOpMathKind::Mul, 1)
+}
 
-	#[test]
-	fn len() {
-		let (runtime, _) = test_runtime();
+pub fn min(
+	_: &Runtime,
+	_: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 0)?;
+	extreme("min", val, Ordering::Less)
+}
 
-		assert_eq!(runtime.run_val("x.len()").unwrap(), Value::Integer(6));
-		assert_eq!(runtime.run_val("y.len()").unwrap(), Value::Integer(9));
-		assert_eq!(runtime.run_val("z.len()").unwrap(), Value::Integer(4));
-		assert_eq!(runtime.run_val("z.child.len()").unwrap(), Value::Integer(2));
-		assert!(runtime.run_val("z.foo.len()").is_err());
-		assert!(runtime.run_val("i.len()").is_err());
-		assert_eq!(
-			runtime.run_val("z[\"child\"].len()").unwrap(),
-			Value::Integer(2)
-		);
+pub fn max(
+	_: &Runtime,
+	_: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 0)?;
+	extreme("max", val, Ordering::Greater)
+}
 
-		assert!(runtime.run_val("x.len(12)").is_err());
-	}
+/// Sorts numbers from smallest to biggest, and strings alphabetically
+pub fn sort(
+	_: &Runtime,
+	_: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 0)?;
+	sort_keyed(
+		array("sort", val)?
+			.iter()
+			.map(|v| (v.clone(), v.clone()))
+			.collect(),
+	)
+}
 
-	#[test]
-	fn index_of() {
-		let (runtime, _) = test_runtime();
+/// Sorts by what the function returns for each value
+pub fn sort_by(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 1)?;
+	let f = fn_arg(runtime, ctx, args, "sort_by")?;
+	let keyed = array("sort_by", val)?
+		.iter()
+		.map(|v| {
+			Ok((
+				call(runtime, &f, vec![Expression::new(v.clone())])?,
+				v.clone(),
+			))
+		})
+		.collect::<Result<Vec<_>>>()?;
+	sort_keyed(keyed)
+}
 
-		assert_eq!(
-			runtime.run_val("x.index_of(200)").unwrap(),
-			Value::Integer(1)
-		);
-		assert_eq!(
-			runtime.run_val("x.index_of(\"bingus\")").unwrap(),
-			Value::Integer(-1)
-		);
-		assert_eq!(
-			runtime.run_val("x.index_of(200)").unwrap(),
-			Value::Integer(1)
-		);
+pub fn reverse(
+	_: &Runtime,
+	_: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 0)?;
+	match val {
+		Value::Array(a) => Ok(Value::Array(a.iter().rev().cloned().collect())),
+		Value::String(s) => Ok(Value::String(s.chars().rev().collect())),
+		_ => Err(no_fn_err("reverse", val)),
 	}
+}
 
-	#[test]
-	fn map_array() {
-		let (runtime, _) = test_runtime();
-		assert_eq!(
-			runtime.run_val("[1, 2, 3].map(v -> v + 1)").unwrap(),
-			Value::Array(vec![
-				Value::Integer(2),
-				Value::Integer(3),
-				Value::Integer(4)
-			])
-		);
-		assert_eq!(
-			runtime.run_val("[1, 2, 3].map(v -> v + \"\")").unwrap(),
-			Value::Array(vec![
-				Value::String("1".to_string()),
-				Value::String("2".to_string()),
-				Value::String("3".to_string())
-			])
-		);
+/// The values from the start index up to, but not including, the end index.
+/// Indexes past the end are treated as the end.
+pub fn slice(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 2)?;
+	let start = get_arg(runtime, ctx, args, 0)?.to_uint()? as usize;
+	let end = get_arg(runtime, ctx, args, 1)?.to_uint()? as usize;
+	let range = |len: usize| start.min(len)..end.clamp(start.min(len), len);
+	match val {
+		Value::Array(a) => Ok(Value::Array(a[range(a.len())].to_vec())),
+		Value::String(s) => {
+			let chars = s.chars().collect::<Vec<char>>();
+			Ok(Value::String(chars[range(chars.len())].iter().collect()))
+		}
+		_ => Err(no_fn_err("slice", val)),
+	}
+}
+
+/// Whether an array has a value, a string has a substring, or an object has
+/// a key
+pub fn contains(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 1)?;
+	let query = get_arg(runtime, ctx, args, 0)?;
+	Ok(Value::Boolean(match val {
+		Value::Array(a) => a.contains(&query),
+		Value::String(s) => s.contains(&query.to_string()?),
+		Value::Object(o) => o.contains_key(&query.to_string()?),
+		_ => return Err(no_fn_err("contains", val)),
+	}))
+}
+
+pub fn any(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 1)?;
+	let f = fn_arg(runtime, ctx, args, "any")?;
+	for (i, v) in array("any", val)?.iter().enumerate() {
+		if test(runtime, &f, i, v)? {
+			return Ok(Value::Boolean(true));
+		}
+	}
+	Ok(Value::Boolean(false))
+}
+
+pub fn all(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 1)?;
+	let f = fn_arg(runtime, ctx, args, "all")?;
+	for (i, v) in array("all", val)?.iter().enumerate() {
+		if !test(runtime, &f, i, v)? {
+			return Ok(Value::Boolean(false));
+		}
+	}
+	Ok(Value::Boolean(true))
+}
+
+/// The first value the function returns true for, or none
+pub fn find(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 1)?;
+	let f = fn_arg(runtime, ctx, args, "find")?;
+	for (i, v) in array("find", val)?.iter().enumerate() {
+		if test(runtime, &f, i, v)? {
+			return Ok(v.clone());
+		}
+	}
+	Ok(Value::None)
+}
+
+/// Joins arrays inside of an array into one, only one level deep
+pub fn flatten(
+	_: &Runtime,
+	_: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 0)?;
+	let mut flat = Vec::new();
+	for v in array("flatten", val)? {
+		match v {
+			Value::Array(inner) => flat.extend(inner.iter().cloned()),
+			v => flat.push(v.clone()),
+		}
+	}
+	Ok(Value::Array(flat))
+}
+
+/// Pairs up the values of two arrays, stopping at the end of the shorter one
+pub fn zip(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 1)?;
+	let other = get_arg(runtime, ctx, args, 0)?;
+	Ok(Value::Array(
+		array("zip", val)?
+			.iter()
+			.zip(other.to_array()?)
+			.map(|(a, b)| Value::Array(vec![a.clone(), b.clone()]))
+			.collect(),
+	))
+}
+
+/// Pairs every value of an array with its index, like `[[0, a], [1, b]]`
+pub fn enumerate(
+	_: &Runtime,
+	_: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 0)?;
+	Ok(Value::Array(
+		array("enumerate", val)?
+			.iter()
+			.enumerate()
+			.map(|(i, v)| Value::Array(vec![Value::Integer(i as i32), v.clone()]))
+			.collect(),
+	))
+}
+
+/// Removes repeated values, keeping the first of each
+pub fn unique(
+	_: &Runtime,
+	_: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 0)?;
+	let mut unique: Vec<Value> = Vec::new();
+	for v in array("unique", val)? {
+		if !unique.contains(v) {
+			unique.push(v.clone());
+		}
+	}
+	Ok(Value::Array(unique))
+}
+
+/// How many values the function returns true for, or how many values are
+/// equal to the argument if it isn't a function
+pub fn count(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 1)?;
+	let query = get_arg(runtime, ctx, args, 0)?;
+	let mut count = 0;
+	for (i, v) in array("count", val)?.iter().enumerate() {
+		let counted = match &query {
+			Value::Function(f) => test(runtime, f, i, v)?,
+			query => v == query,
+		};
+		if counted {
+			count += 1;
+		}
+	}
+	Ok(Value::Integer(count))
+}
+
+/// A value from the array at random, or an array of `n` different values
+pub fn pick(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len_between(args, 0, 1)?;
+	let items = array("pick", val)?;
+	if args.is_empty() {
+		if items.is_empty() {
+			return Err(RuntimeError::BadStdFnCall(
+				"cannot pick from an empty array".to_string(),
+			));
+		}
+		let (rolls, mut picked) = draw(runtime, items, 1)?;
+		return Ok(record(runtime, "pick".to_string(), rolls, picked.remove(0)));
+	}
+	let n = get_arg(runtime, ctx, args, 0)?.to_uint()? as usize;
+	let (rolls, picked) = draw(runtime, items, n)?;
+	Ok(record(
+		runtime,
+		format!("pick({})", n),
+		rolls,
+		Value::Array(picked),
+	))
+}
+
+pub fn shuffle(
+	runtime: &Runtime,
+	_: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 0)?;
+	let items = array("shuffle", val)?;
+	let (rolls, shuffled) = draw(runtime, items, items.len())?;
+	Ok(record(
+		runtime,
+		"shuffle".to_string(),
+		rolls,
+		Value::Array(shuffled),
+	))
+}
+
+/// A value from the array at random, where values with bigger weights are
+/// more likely, like `loot.weighted_pick([5, 3, 1])`
+pub fn weighted_pick(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 1)?;
+	let items = array("weighted_pick", val)?;
+	let weights = match get_arg(runtime, ctx, args, 0)? {
+		Value::Array(weights) if weights.len() == items.len() => weights
+			.iter()
+			.map(|w| w.to_uint())
+			.collect::<Result<Vec<u32>>>()?,
+		_ => {
+			return Err(RuntimeError::BadStdFnCall(
+				"weights must be an array with a weight for every value".to_string(),
+			))
+		}
+	};
+	let total = weights.iter().sum::<u32>();
+	if total == 0 {
+		return Err(RuntimeError::BadStdFnCall(
+			"weights must add up to more than 0".to_string(),
+		));
+	}
+	let roll = Roll::die(runtime, total);
+	let mut left = roll.value();
+	let mut picked = 0;
+	for (i, w) in weights.iter().enumerate() {
+		if left <= *w {
+			picked = i;
+			break;
+		}
+		left -= w;
+	}
+	Ok(record(
+		runtime,
+		"weighted_pick".to_string(),
+		vec![roll],
+		items[picked].clone(),
+	))
+}
+
+pub fn upper(
+	_: &Runtime,
+	_: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 0)?;
+	Ok(Value::String(text("upper", val)?.to_uppercase()))
+}
+
+pub fn lower(
+	_: &Runtime,
+	_: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 0)?;
+	Ok(Value::String(text("lower", val)?.to_lowercase()))
+}
+
+/// Removes whitespace from both ends of a string
+pub fn trim(
+	_: &Runtime,
+	_: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 0)?;
+	Ok(Value::String(text("trim", val)?.trim().to_string()))
+}
+
+/// Splits a string on every separator. An empty separator splits it into
+/// characters.
+pub fn split(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 1)?;
+	let s = text("split", val)?;
+	let separator = get_arg(runtime, ctx, args, 0)?.to_string()?;
+	if separator.is_empty() {
+		return chars(runtime, ctx, &Vec::new(), val);
+	}
+	Ok(Value::Array(
+		s.split(separator.as_str())
+			.map(|part| Value::String(part.to_string()))
+			.collect(),
+	))
+}
+
+/// Joins an array into a string, with a separator between every value
+pub fn join(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 1)?;
+	let separator = get_arg(runtime, ctx, args, 0)?.to_string()?;
+	Ok(Value::String(
+		array("join", val)?
+			.iter()
+			.map(|v| v.to_string())
+			.collect::<Result<Vec<String>>>()?
+			.join(&separator),
+	))
+}
+
+/// Replaces every match of a string with another one
+pub fn replace(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 2)?;
+	let s = text("replace", val)?;
+	let from = get_arg(runtime, ctx, args, 0)?.to_string()?;
+	let to = get_arg(runtime, ctx, args, 1)?.to_string()?;
+	Ok(Value::String(s.replace(&from, &to)))
+}
+
+pub fn starts_with(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 1)?;
+	let s = text("starts_with", val)?;
+	let prefix = get_arg(runtime, ctx, args, 0)?.to_string()?;
+	Ok(Value::Boolean(s.starts_with(&prefix)))
+}
+
+pub fn ends_with(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 1)?;
+	let s = text("ends_with", val)?;
+	let suffix = get_arg(runtime, ctx, args, 0)?.to_string()?;
+	Ok(Value::Boolean(s.ends_with(&suffix)))
+}
+
+pub fn repeat(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 1)?;
+	let s = text("repeat", val)?;
+	let times = get_arg(runtime, ctx, args, 0)?.to_uint()? as usize;
+	Ok(Value::String(s.repeat(times)))
+}
+
+/// Fills the start of a string up to a width, with spaces or a character
+pub fn pad_left(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	pad(runtime, ctx, args, "pad_left", val, true)
+}
+
+/// Fills the end of a string up to a width, with spaces or a character
+pub fn pad_right(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	pad(runtime, ctx, args, "pad_right", val, false)
+}
+
+/// Every character of a string, as strings
+pub fn chars(
+	_: &Runtime,
+	_: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 0)?;
+	Ok(Value::Array(
+		text("chars", val)?
+			.chars()
+			.map(|c| Value::String(c.to_string()))
+			.collect(),
+	))
+}
+
+/// The names of an object's fields, in alphabetical order
+pub fn keys(
+	_: &Runtime,
+	_: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 0)?;
+	Ok(Value::Array(
+		object_entries("keys", val)?
+			.into_iter()
+			.map(|(k, _)| Value::String(k.clone()))
+			.collect(),
+	))
+}
+
+/// The values of an object's fields, in the alphabetical order of their names
+pub fn values(
+	_: &Runtime,
+	_: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 0)?;
+	Ok(Value::Array(
+		object_entries("values", val)?
+			.into_iter()
+			.map(|(_, v)| v.clone())
+			.collect(),
+	))
+}
+
+/// An object's fields as `[name, value]` pairs, in alphabetical order
+pub fn entries(
+	_: &Runtime,
+	_: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 0)?;
+	Ok(Value::Array(
+		object_entries("entries", val)?
+			.into_iter()
+			.map(|(k, v)| Value::Array(vec![Value::String(k.clone()), v.clone()]))
+			.collect(),
+	))
+}
+
+/// Makes an object out of `[name, value]` pairs. Later pairs replace earlier
+/// ones with the same name.
+pub fn from_entries(
+	_: &Runtime,
+	_: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 0)?;
+	let mut object = HashMap::new();
+	for entry in array("from_entries", val)? {
+		match entry.to_array()?.as_slice() {
+			[k, v] => object.insert(k.to_string()?, v.clone()),
+			_ => {
+				return Err(RuntimeError::BadStdFnCall(
+					".from_entries() needs [name, value] pairs".to_string(),
+				))
+			}
+		};
+	}
+	Ok(Value::Object(object))
+}
+
+pub fn has(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 1)?;
+	let key = get_arg(runtime, ctx, args, 0)?.to_string()?;
+	match val {
+		Value::Object(object) => Ok(Value::Boolean(object.contains_key(&key))),
+		_ => Err(no_fn_err("has", val)),
+	}
+}
+
+/// Combines two objects, with the fields of the second replacing the first's.
+/// `merge(other, true)` also merges objects inside of them.
+pub fn merge(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len_between(args, 1, 2)?;
+	let other = get_arg(runtime, ctx, args, 0)?;
+	let deep = match args.len() {
+		2 => get_arg(runtime, ctx, args, 1)?.to_boolean()?,
+		_ => false,
+	};
+	match val {
+		Value::Object(object) => Ok(Value::Object(merge_objects(
+			object,
+			other.to_object()?,
+			deep,
+		))),
+		_ => Err(no_fn_err("merge", val)),
+	}
+}
+
+/// An object without some fields, given as a name or an array of names
+pub fn without(
+	runtime: &Runtime,
+	ctx: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 1)?;
+	let keys = match get_arg(runtime, ctx, args, 0)? {
+		Value::Array(keys) => keys
+			.iter()
+			.map(|k| k.to_string())
+			.collect::<Result<Vec<String>>>()?,
+		key => vec![key.to_string()?],
+	};
+	match val {
+		Value::Object(object) => {
+			let mut object = object.clone();
+			for key in keys {
+				object.remove(&key);
+			}
+			Ok(Value::Object(object))
+		}
+		_ => Err(no_fn_err("without", val)),
+	}
+}
+
+/// Strings are read as numbers, everything else is converted like it would be
+/// for math. Decimals are truncated.
+pub fn int(
+	_: &Runtime,
+	_: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 0)?;
+	match val {
+		Value::String(s) => {
+			let s = s.trim();
+			s.parse::<i32>()
+				.or_else(|_| s.parse::<f32>().map(|d| d.trunc() as i32))
+				.map(Value::Integer)
+				.map_err(|_| {
+					RuntimeError::CannotParse(s.to_string(), ValueKind::Integer)
+				})
+		}
+		_ => val
+			.to_integer()
+			.map(Value::Integer)
+			.map_err(|_| cast_err(val, ValueKind::Integer)),
+	}
+}
+
+pub fn decimal(
+	_: &Runtime,
+	_: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 0)?;
+	match val {
+		Value::String(s) => s
+			.trim()
+			.parse::<f32>()
+			.map(Value::Decimal)
+			.map_err(|_| RuntimeError::CannotParse(s.clone(), ValueKind::Decimal)),
+		_ => val
+			.to_decimal()
+			.map(Value::Decimal)
+			.map_err(|_| cast_err(val, ValueKind::Decimal)),
+	}
+}
+
+/// Rolls become their total
+pub fn string(
+	_: &Runtime,
+	_: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 0)?;
+	val
+		.to_string()
+		.map(Value::String)
+		.map_err(|_| cast_err(val, ValueKind::String))
+}
+
+/// Values are true unless they're zero or empty, so `"false".bool()` is true
+pub fn bool(
+	_: &Runtime,
+	_: &RuntimeContext,
+	args: &Vec<Expression<Op, ExpressionToken>>,
+	val: &Value,
+) -> Result<Value> {
+	enforce_len(args, 0)?;
+	val.to_boolean().map(Value::Boolean)
+}
+
+#[cfg(test)]
+mod test {
+
+	use super::*;
+	use crate::runtime::assert_cases;
+
+	fn test_runtime() -> (Runtime, RuntimeContext) {
+		let mut r = Runtime::new(rand::thread_rng(), ());
+		r.load(
+			r#"
+			x = [100, 200, 300, 400, 500, 600];
+			y = "123456789";
+			z = { foo: 10, bar: 100, baz: 1000, child: ["first", "second"] };
+			i = 45;
+			"#,
+			"",
+		)
+		.unwrap();
+		(r, RuntimeContext::new())
+	}
+
+	#[test]
+	fn len() {
+		let (runtime, _) = test_runtime();
+
+		assert_eq!(runtime.run_val("x.len()").unwrap(), Value::Integer(6));
+		assert_eq!(runtime.run_val("y.len()").unwrap(), Value::Integer(9));
+		assert_eq!(runtime.run_val("z.len()").unwrap(), Value::Integer(4));
+		assert_eq!(runtime.run_val("z.child.len()").unwrap(), Value::Integer(2));
+		assert!(runtime.run_val("z.foo.len()").is_err());
+		assert!(runtime.run_val("i.len()").is_err());
+		assert_eq!(
+			runtime.run_val("z[\"child\"].len()").unwrap(),
+			Value::Integer(2)
+		);
+
+		assert!(runtime.run_val("x.len(12)").is_err());
+	}
+
+	#[test]
+	fn index_of() {
+		let (runtime, _) = test_runtime();
+		assert_cases(
+			&runtime,
+			&[
+				("x.index_of(200)", Value::Integer(1)),
+				("[1, 2, 1].index_of(1)", Value::Integer(0)),
+				// values that aren't in the array give none, not an error
+				("x.index_of(\"bingus\")", Value::None),
+				("[].index_of(1)", Value::None),
+				("x.index_of(201) ?? -1", Value::Integer(-1)),
+			],
+		);
+		assert!(matches!(
+			runtime.run_val("y.index_of(\"2\")"),
+			Err(RuntimeError::NoStdFnForKind(name, ValueKind::String)) if name == "index_of"
+		));
+		assert!(runtime.run_val("x.index_of(1, 2)").is_err());
+	}
+
+	#[test]
+	fn map_array() {
+		let (runtime, _) = test_runtime();
+		assert_eq!(
+			runtime.run_val("[1, 2, 3].map(v -> v + 1)").unwrap(),
+			Value::Array(vec![
+				Value::Integer(2),
+				Value::Integer(3),
+				Value::Integer(4)
+			])
+		);
+		assert_eq!(
+			runtime.run_val("[1, 2, 3].map(v -> v + \"\")").unwrap(),
+			Value::Array(vec![
+				Value::String("1".to_string()),
+				Value::String("2".to_string()),
+				Value::String("3".to_string())
+			])
+		);
 		assert_eq!(
 			runtime.run_val("[1, 2, 3].map(v i -> v + i)").unwrap(),
 			Value::Array(vec![
@@ -359,4 +1585,494 @@ mod test {
 			)
 		);
 	}
+
+	#[test]
+	fn casts() {
+		let (runtime, _) = test_runtime();
+		assert_cases(
+			&runtime,
+			&[
+				("\"12\".int()", Value::Integer(12)),
+				("\" 7.9 \".int()", Value::Integer(7)),
+				("3.7.int()", Value::Integer(3)),
+				("true.int()", Value::Integer(1)),
+				("i.decimal()", Value::Decimal(45.0)),
+				("\"0.5\".decimal()", Value::Decimal(0.5)),
+				("i.string()", Value::String("45".to_string())),
+				("bool(0)", Value::Boolean(false)),
+				("\"false\".bool()", Value::Boolean(true)),
+				("int(\"12\") + 1", Value::Integer(13)),
+				("string(i) + \"!\"", Value::String("45!".to_string())),
+			],
+		);
+		assert!((1..=6)
+			.contains(&runtime.run_val("int(1d6)").unwrap().to_integer().unwrap()));
+		assert!(matches!(
+			runtime.run_val("y.int()"),
+			Ok(Value::Integer(123456789))
+		));
+		assert!(matches!(
+			runtime.run_val("\"many\".int()"),
+			Err(RuntimeError::CannotParse(_, ValueKind::Integer))
+		));
+		assert!(matches!(
+			runtime.run_val("x.int()"),
+			Err(RuntimeError::CannotCast(
+				ValueKind::Array,
+				ValueKind::Integer
+			))
+		));
+		assert!(matches!(
+			runtime.run_val("z.string()"),
+			Err(RuntimeError::CannotCast(
+				ValueKind::Object,
+				ValueKind::String
+			))
+		));
+		assert!(runtime.run_val("int()").is_err());
+		assert!(runtime.run_val("int").is_err());
+	}
+
+	#[test]
+	fn strict_mode() {
+		let (mut runtime, _) = test_runtime();
+		assert!(runtime.run_val("\"Level \" + i").is_ok());
+		runtime.strict = true;
+		assert!(matches!(
+			runtime.run_val("\"Level \" + i"),
+			Err(RuntimeError::StrictCoercion(..))
+		));
+		assert!(runtime.run_val("1d20 + \"\"").is_err());
+		assert_eq!(
+			runtime.run_val("\"Level \" + i.string()").unwrap(),
+			Value::String("Level 45".to_string())
+		);
+		assert_eq!(runtime.run_val("i + 1").unwrap(), Value::Integer(46));
+	}
+
+	#[test]
+	fn get() {
+		let (runtime, _) = test_runtime();
+		assert_cases(
+			&runtime,
+			&[
+				("x.get(1)", Value::Integer(200)),
+				("x.get(10)", Value::None),
+				("z.get(\"foo\")", Value::Integer(10)),
+				("z.get(\"nothing\")", Value::None),
+				("y.get(0)", Value::String("1".to_string())),
+				("y.get(100)", Value::None),
+				("z.get(\"nothing\").get(0)", Value::None),
+			],
+		);
+		assert!(runtime.run_val("i.get(0)").is_err());
+		assert!(runtime.run_val("x.get(-1)").is_err());
+	}
+
+	fn ints(values: &[i32]) -> Value {
+		Value::Array(values.iter().map(|v| Value::Integer(*v)).collect())
+	}
+
+	#[test]
+	fn arrays() {
+		let (runtime, _) = test_runtime();
+		assert_cases(
+			&runtime,
+			&[
+				("x.filter(v -> v > 300)", ints(&[400, 500, 600])),
+				("x.filter(v i -> i < 2)", ints(&[100, 200])),
+				("[1, 2, 3].sum()", Value::Integer(6)),
+				("[1, 2.5].sum()", Value::Decimal(3.5)),
+				("[].sum()", Value::Integer(0)),
+				("[2, 3, 4].product()", Value::Integer(24)),
+				("[3, 1, 2].min()", Value::Integer(1)),
+				("[3, 1.5, 2].max()", Value::Integer(3)),
+				("[].max()", Value::None),
+				("[3, 1, 2].sort()", ints(&[1, 2, 3])),
+				("[3, 1, 2].sort_by(v -> 0 - v)", ints(&[3, 2, 1])),
+				("[1, 2, 3].reverse()", ints(&[3, 2, 1])),
+				("\"abc\".reverse()", Value::String("cba".to_string())),
+				("x.slice(1, 3)", ints(&[200, 300])),
+				("x.slice(4, 100)", ints(&[500, 600])),
+				("x.slice(3, 1)", ints(&[])),
+				("y.slice(0, 3)", Value::String("123".to_string())),
+				("x.contains(300)", Value::Boolean(true)),
+				("x.contains(301)", Value::Boolean(false)),
+				("y.contains(\"345\")", Value::Boolean(true)),
+				("z.contains(\"foo\")", Value::Boolean(true)),
+				("x.any(v -> v > 500)", Value::Boolean(true)),
+				("x.all(v -> v > 100)", Value::Boolean(false)),
+				("[].all(v -> v > 100)", Value::Boolean(true)),
+				("x.find(v -> v > 250)", Value::Integer(300)),
+				("x.find(v -> v > 1000)", Value::None),
+				("[[1, 2], [3], 4].flatten()", ints(&[1, 2, 3, 4])),
+				(
+					"[1, 2, 3].zip([4, 5])",
+					Value::Array(vec![ints(&[1, 4]), ints(&[2, 5])]),
+				),
+				(
+					"[5, 6].enumerate()",
+					Value::Array(vec![ints(&[0, 5]), ints(&[1, 6])]),
+				),
+				("[1, 2, 1, 3, 2].unique()", ints(&[1, 2, 3])),
+				("[1, 2, 1, 3].count(1)", Value::Integer(2)),
+				("x.count(v -> v >= 300)", Value::Integer(4)),
+			],
+		);
+		assert_eq!(
+			runtime.run_val("[\"b\", \"c\", \"a\"].sort()").unwrap(),
+			Value::Array(
+				["a", "b", "c"]
+					.iter()
+					.map(|s| Value::String(s.to_string()))
+					.collect()
+			)
+		);
+		assert!((3..=18).contains(
+			&runtime
+				.run_val("[1d6, 1d6, 1d6].sum()")
+				.unwrap()
+				.to_integer()
+				.unwrap()
+		));
+		assert!(runtime.run_val("[1, \"a\"].sum()").is_err());
+		assert!(runtime.run_val("[1, \"a\"].sort()").is_err());
+		assert!(runtime.run_val("i.sum()").is_err());
+		assert!(runtime.run_val("x.filter(3)").is_err());
+		assert!(runtime.run_val("x.filter()").is_err());
+		assert!(runtime.run_val("x.slice(1)").is_err());
+		assert!(runtime.run_val("x.sort(1)").is_err());
+	}
+
+	#[test]
+	fn empty_arrays() {
+		let (runtime, _) = test_runtime();
+		assert_cases(
+			&runtime,
+			&[
+				("[].len()", Value::Integer(0)),
+				("[].sum()", Value::Integer(0)),
+				("[].product()", Value::Integer(1)),
+				("[].min()", Value::None),
+				("[].max()", Value::None),
+				("[].sort()", ints(&[])),
+				("[].reverse()", ints(&[])),
+				("[].unique()", ints(&[])),
+				("[].flatten()", ints(&[])),
+				("[].slice(0, 2)", ints(&[])),
+				("[].map(v -> v + 1)", ints(&[])),
+				("[].filter(v -> v > 1)", ints(&[])),
+				("[].fold(0, a b -> a + b)", Value::Integer(0)),
+				("[].any(v -> v > 1)", Value::Boolean(false)),
+				("[].find(v -> v > 1)", Value::None),
+				("[].contains(1)", Value::Boolean(false)),
+				("[].count(1)", Value::Integer(0)),
+				("[].zip([1])", ints(&[])),
+				("[].enumerate()", ints(&[])),
+				("[].shuffle()", ints(&[])),
+			],
+		);
+		assert!(runtime.run_val("[].pick()").is_err());
+	}
+
+	#[test]
+	fn wrong_argument_types() {
+		let (runtime, _) = test_runtime();
+		let wrong_kind = |expr: &str| {
+			matches!(
+				runtime.run_val(expr),
+				Err(RuntimeError::WrongExpectedValue(
+					ValueKind::Integer,
+					ValueKind::String
+				))
+			)
+		};
+		for expr in &[
+			"x.slice(\"a\", 2)",
+			"x.get(\"a\")",
+			"x.pick(\"a\")",
+			"y.repeat(\"b\")",
+			"y.pad_left(\"b\")",
+		] {
+			assert!(wrong_kind(expr), "{}", expr);
+		}
+		// functions are the only thing these take
+		for expr in &["x.filter(3)", "x.sort_by(2)", "x.map(1)", "x.any(true)"] {
+			assert!(runtime.run_val(expr).is_err(), "{}", expr);
+		}
+		assert!(runtime.run_val("[1, \"a\"].sum()").is_err());
+		assert!(runtime.run_val("x.weighted_pick(1)").is_err());
+		assert!(runtime.run_val("z.sum()").is_err());
+	}
+
+	fn strings(values: &[&str]) -> Value {
+		Value::Array(
+			values
+				.iter()
+				.map(|v| Value::String(v.to_string()))
+				.collect(),
+		)
+	}
+
+	#[test]
+	fn string_fns() {
+		let (runtime, _) = test_runtime();
+		let string = |s: &str| Value::String(s.to_string());
+		assert_cases(
+			&runtime,
+			&[
+				(r#""Élodie".upper()"#, string("ÉLODIE")),
+				(r#""ÉLODIE".lower()"#, string("élodie")),
+				(r#""  hi ".trim()"#, string("hi")),
+				(r#""a,b,,c".split(",")"#, strings(&["a", "b", "", "c"])),
+				(r#""añb".split("")"#, strings(&["a", "ñ", "b"])),
+				(r#"["Str", 16].join(": ")"#, string("Str: 16")),
+				(r#"[].join(", ")"#, string("")),
+				(r#""1d6 fire".replace("fire", "cold")"#, string("1d6 cold")),
+				(r#""Longsword".starts_with("Long")"#, Value::Boolean(true)),
+				(r#""Longsword".ends_with("Long")"#, Value::Boolean(false)),
+				(r#""ñ".contains("ñ")"#, Value::Boolean(true)),
+				(r#""ab".repeat(3)"#, string("ababab")),
+				(r#""7".pad_left(3, "0")"#, string("007")),
+				(r#""ñ".pad_right(3)"#, string("ñ  ")),
+				(r#""long".pad_left(2)"#, string("long")),
+				(r#""añ".chars()"#, strings(&["a", "ñ"])),
+				(r#""añb".len()"#, Value::Integer(3)),
+				(r#""añb"[2]"#, string("b")),
+			],
+		);
+		assert!(runtime.run_val(r#""a".pad_left(3, "ab")"#).is_err());
+		assert!(runtime.run_val(r#""a".pad_left()"#).is_err());
+		assert!(runtime.run_val(r#""a".repeat(-1)"#).is_err());
+		assert!(runtime.run_val("x.upper()").is_err());
+		assert!(runtime.run_val("[x].join(\",\")").is_err());
+		assert!(matches!(
+			runtime.run_val(r#""añ"[2]"#),
+			Err(RuntimeError::IndexOutOfBounds(2, 2))
+		));
+	}
+
+	fn object(fields: &[(&str, Value)]) -> Value {
+		Value::Object(
+			fields
+				.iter()
+				.map(|(k, v)| (k.to_string(), v.clone()))
+				.collect(),
+		)
+	}
+
+	#[test]
+	fn objects() {
+		let (runtime, _) = test_runtime();
+		let string = |s: &str| Value::String(s.to_string());
+		assert_cases(
+			&runtime,
+			&[
+				("z.keys()", strings(&["bar", "baz", "child", "foo"])),
+				("{b: 2, a: 1}.values()", ints(&[1, 2])),
+				(
+					"{b: 2, a: 1}.entries()",
+					Value::Array(vec![
+						Value::Array(vec![string("a"), Value::Integer(1)]),
+						Value::Array(vec![string("b"), Value::Integer(2)]),
+					]),
+				),
+				(
+					"[[\"a\", 1], [\"b\", 2], [\"a\", 3]].from_entries()",
+					object(&[("a", Value::Integer(3)), ("b", Value::Integer(2))]),
+				),
+				("z.has(\"foo\")", Value::Boolean(true)),
+				("z.has(\"qux\")", Value::Boolean(false)),
+				("z.get(\"qux\", 5)", Value::Integer(5)),
+				("z.get(\"foo\", 5)", Value::Integer(10)),
+				("x.get(10, 0)", Value::Integer(0)),
+				(
+					"{str: 10, dex: 12}.merge({dex: 14, con: 8})",
+					object(&[
+						("str", Value::Integer(10)),
+						("dex", Value::Integer(14)),
+						("con", Value::Integer(8)),
+					]),
+				),
+				(
+					"{a: {b: 1, c: 2}}.merge({a: {c: 3}})",
+					object(&[("a", object(&[("c", Value::Integer(3))]))]),
+				),
+				(
+					"{a: {b: 1, c: 2}}.merge({a: {c: 3}}, true)",
+					object(&[(
+						"a",
+						object(&[("b", Value::Integer(1)), ("c", Value::Integer(3))]),
+					)]),
+				),
+				(
+					"{a: 1, b: 2, c: 3}.without([\"a\", \"c\"])",
+					object(&[("b", Value::Integer(2))]),
+				),
+				(
+					"{a: 1, b: 2}.without(\"a\")",
+					object(&[("b", Value::Integer(2))]),
+				),
+				(
+					"{a: 1, b: 2, c: 3}.filter(v k -> k == \"b\")",
+					object(&[("b", Value::Integer(2))]),
+				),
+				(
+					"{a: 1, b: 2, c: 3}.filter(v -> v >= 2)",
+					object(&[("b", Value::Integer(2)), ("c", Value::Integer(3))]),
+				),
+			],
+		);
+		assert!(runtime.run_val("x.keys()").is_err());
+		assert!(runtime.run_val("z.merge(x)").is_err());
+		assert!(runtime.run_val("[[1, 2, 3]].from_entries()").is_err());
+		assert!(runtime.run_val("z.get()").is_err());
+	}
+
+	#[test]
+	fn random_picks() {
+		let (mut runtime, _) = test_runtime();
+		let items = match runtime.run_val("x").unwrap() {
+			Value::Array(items) => items,
+			_ => unreachable!(),
+		};
+		for _ in 0..20 {
+			let picked = runtime.run_val("x.pick()").unwrap();
+			let draws = runtime.draws.borrow().clone();
+			assert_eq!(draws.len(), 1);
+			assert_eq!(draws[0].name, "pick");
+			assert_eq!(draws[0].rolls[0].sides, 6);
+			assert_eq!(picked, items[draws[0].rolls[0].value() as usize - 1]);
+			assert_eq!(draws[0].value, picked);
+		}
+
+		let picked = match runtime.run_val("x.pick(3)").unwrap() {
+			Value::Array(picked) => picked,
+			other => panic!("Expected an array, got {}", other),
+		};
+		assert_eq!(picked.len(), 3);
+		assert!(picked.iter().all(|v| items.contains(v)));
+		assert!(!picked[1..].contains(&picked[0]) && picked[1] != picked[2]);
+		let sides = runtime.draws.borrow()[0]
+			.rolls
+			.iter()
+			.map(|r| r.sides)
+			.collect::<Vec<u32>>();
+		assert_eq!(sides, vec![6, 5, 4]);
+
+		assert_eq!(
+			runtime.run_val("x.shuffle().sort()").unwrap(),
+			Value::Array(items)
+		);
+		for _ in 0..20 {
+			assert_ne!(
+				runtime
+					.run_val("[\"common\", \"never\", \"rare\"].weighted_pick([3, 0, 1])")
+					.unwrap(),
+				Value::String("never".to_string())
+			);
+		}
+		assert_eq!(
+			runtime
+				.run_val("[\"a\", \"b\"].weighted_pick([0, 5])")
+				.unwrap(),
+			Value::String("b".to_string())
+		);
+		assert_eq!(runtime.draws.borrow()[0].rolls[0].sides, 5);
+		// draws are forgotten every time something is run
+		runtime.run_val("x.len()").unwrap();
+		assert!(runtime.draws.borrow().is_empty());
+
+		assert!(runtime.run_val("[].pick()").is_err());
+		assert!(runtime.run_val("x.pick(7)").is_err());
+		assert!(runtime.run_val("x.weighted_pick([1, 2])").is_err());
+		assert!(runtime.run_val("[1, 2].weighted_pick([0, 0])").is_err());
+		assert!(runtime.run_val("i.pick()").is_err());
+
+		// variables that pick at random aren't cached
+		runtime
+			.load("loot = [1, 2, 3].pick(); sorted = [3, 1].sort();", "")
+			.unwrap();
+		assert!(!runtime.graph.is_pure("loot"));
+		assert!(runtime.graph.is_pure("sorted"));
+	}
+
+	#[test]
+	fn native_fns() {
+		use std::{cell::Cell, rc::Rc};
+
+		let (mut runtime, _) = test_runtime();
+		let prices = [("sword", 15), ("rope", 1)]
+			.iter()
+			.map(|(k, v)| (k.to_string(), *v))
+			.collect::<HashMap<String, i32>>();
+		runtime.register_fn(
+			"price",
+			NativeFn::new(vec![Type::String], Type::Integer, move |args| {
+				Ok(Value::Integer(
+					*prices.get(&args[0].to_string()?).unwrap_or(&0),
+				))
+			})
+			.pure(),
+		);
+		let calls = Rc::new(Cell::new(0));
+		let counter = calls.clone();
+		runtime.register_fn(
+			"next_id",
+			NativeFn::new(vec![], Type::Integer, move |_| {
+				counter.set(counter.get() + 1);
+				Ok(Value::Integer(counter.get()))
+			}),
+		);
+		runtime.register_fn(
+			"len",
+			NativeFn::new(vec![Type::Any], Type::Integer, |_| Ok(Value::Integer(-1))),
+		);
+		runtime.register_fn(
+			"broken",
+			NativeFn::new(vec![], Type::Integer, |_| {
+				Ok(Value::String("oops".to_string()))
+			}),
+		);
+
+		assert_eq!(
+			runtime.run_val("\"sword\".price()").unwrap(),
+			Value::Integer(15)
+		);
+		assert_eq!(
+			runtime.run_val("price(\"rope\") * 2").unwrap(),
+			Value::Integer(2)
+		);
+		assert_eq!(runtime.run_val("next_id()").unwrap(), Value::Integer(1));
+		assert_eq!(runtime.run_val("next_id()").unwrap(), Value::Integer(2));
+		// registered functions replace standard ones
+		assert_eq!(runtime.run_val("x.len()").unwrap(), Value::Integer(-1));
+		assert!(matches!(
+			runtime.run_val("price(3)"),
+			Err(RuntimeError::WrongArgumentType(..))
+		));
+		assert!(runtime.run_val("price(\"a\", \"b\")").is_err());
+		assert!(runtime.run_val("price").is_err());
+		assert!(matches!(
+			runtime.run_val("broken()"),
+			Err(RuntimeError::WrongReturnType(..))
+		));
+
+		// variables that call functions that aren't pure aren't cached
+		runtime
+			.load("id = next_id(); cost = price(\"sword\");", "")
+			.unwrap();
+		assert!(!runtime.graph.is_pure("id"));
+		assert!(runtime.graph.is_pure("cost"));
+		let first = runtime.run_val("id").unwrap();
+		assert_ne!(runtime.run_val("id").unwrap(), first);
+
+		runtime
+			.load("bad = price(3); fine = next_id();", "")
+			.unwrap();
+		let problems = runtime.check();
+		assert_eq!(problems.len(), 1);
+		assert_eq!(problems[0].location, "bad");
+		assert_eq!(calls.get(), 4);
+	}
 }
@@ -1,53 +1,425 @@
 use std::{collections::HashMap, fmt::Display};
 
+use ivory_expression::Expression;
 use ivory_tokenizer::{
-	istruct::StructName, values::struct_instance::StructInstance,
+	istruct::{StructDefinitionValue, StructName},
+	itype::Type,
+	values::struct_instance::StructInstance,
 };
 
 use crate::{
+	error::FieldError,
 	runtime::{Runtime, RuntimeContext},
 	value::Value,
 	Result, RuntimeError,
 };
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct StructValue {
 	pub kind: StructName,
 	pub values: HashMap<String, Value>,
 }
 
 impl StructValue {
+	/// Evaluates a struct instance, checking its fields against the struct's
+	/// definition and filling in defaults for the ones it leaves out. Every
+	/// problem with the fields is reported at once.
 	pub fn build(
 		runtime: &Runtime,
 		ctx: &RuntimeContext,
 		instance: &StructInstance,
 	) -> Result<Self> {
-		if let Some(def) = runtime.values.structs.get(&instance.name.0) {
-			let mut values = HashMap::new();
-			for (name, expr) in instance.values.iter() {
-				// Return an error if the struct instance has a field in it that isn't
-				// present in the struct definition
-				if !def.values.iter().any(|(def_name, _)| def_name.0 == name.0) {
-					return Err(RuntimeError::FieldNotOnStruct(
-						def.name.0.clone(),
-						name.0.clone(),
-					));
+		let fields = runtime.values.struct_fields(&instance.name.0)?;
+
+		let mut errors = Vec::new();
+		let mut left_out = Vec::new();
+		let mut values = HashMap::new();
+		for (name, expr) in instance.values.iter() {
+			if !fields.iter().any(|(def_name, _)| def_name == name) {
+				errors.push(FieldError::NotOnStruct(name.0.clone()));
+			}
+			values.insert(name.0.clone(), runtime.evaluate(ctx, expr)?);
+		}
+		for (name, def_value) in fields.iter() {
+			if let StructDefinitionValue::Type(t) = def_value {
+				match values.get(&name.0) {
+					Some(value) if !value.is_type(t, &runtime.values) => errors.push(
+						FieldError::WrongType(name.0.clone(), t.clone(), value.kind()),
+					),
+					Some(_) => {}
+					None if matches!(t, Type::Optional(_)) => {
+						left_out.push(name.0.clone())
+					}
+					None => errors.push(FieldError::Missing(name.0.clone(), t.clone())),
 				}
-				values.insert(name.0.clone(), runtime.evaluate(ctx, expr)?);
 			}
-			Ok(Self {
-				kind: instance.name.clone(),
-				values,
-			})
-		} else {
-			Err(RuntimeError::StructNotFound(instance.name.0.clone()))
 		}
+		if !errors.is_empty() {
+			return Err(RuntimeError::InvalidStruct(instance.name.0.clone(), errors));
+		}
+		// optional fields that are left out are none
+		for name in left_out {
+			values.insert(name, Value::None);
+		}
+
+		// Defaults are filled in the order they're defined in, and can use the
+		// fields of the instance and any default defined before them
+		let mut field_ctx = RuntimeContext::new();
+		for (name, value) in values.iter() {
+			field_ctx
+				.params
+				.insert(name.clone(), Expression::new(value.clone()));
+		}
+		for (name, def_value) in fields.iter() {
+			if let StructDefinitionValue::Value(expr) = def_value {
+				if !values.contains_key(&name.0) {
+					let value = runtime.evaluate(&field_ctx, expr)?;
+					field_ctx
+						.params
+						.insert(name.0.clone(), Expression::new(value.clone()));
+					values.insert(name.0.clone(), value);
+				}
+			}
+		}
+
+		Ok(Self {
+			kind: instance.name.clone(),
+			values,
+		})
+	}
+
+	/// Every field of the instance, for calling a function stored in one of
+	/// them.
+	pub fn scope(&self) -> RuntimeContext {
+		let mut ctx = RuntimeContext::new();
+		for (name, value) in &self.values {
+			ctx
+				.params
+				.insert(name.clone(), Expression::new(value.clone()));
+		}
+		ctx
 	}
 }
 
 impl Display for StructValue {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		// TODO
-		write!(f, "<struct value>")
+		let mut values = self.values.iter().collect::<Vec<(&String, &Value)>>();
+		values.sort_by(|a, b| a.0.cmp(b.0));
+		if values.is_empty() {
+			write!(f, "{} {{}}", self.kind)
+		} else {
+			write!(
+				f,
+				"{} {{ {} }}",
+				self.kind,
+				values
+					.iter()
+					.map(|(k, v)| format!("{}: {}", k, v))
+					.collect::<Vec<String>>()
+					.join(", ")
+			)
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::{
+		error::FieldError,
+		runtime::Runtime,
+		value::{Value, ValueKind},
+		RuntimeError,
+	};
+
+	fn runtime() -> Runtime {
+		let mut runtime = Runtime::new(rand::thread_rng(), ());
+		runtime
+			.load(
+				r#"
+		struct Item { name: string, weight: decimal }
+		struct Ability { score: int, mod: (score - 10) /_ 2 }
+		struct Weapon {
+			name: string,
+			bonus: 0,
+			damage: 1d8 + bonus,
+			describe: n -> name + " x" + n
+		}
+		struct Character {
+			name: string,
+			level: int,
+			items: Item[],
+			stats: int[][],
+			notes: any
+		}
+		sword = Item { name: "Sword", weight: 3 };
+
+		impl Weapon {
+			attack = hit -> 1d20 + hit + self.bonus;
+			average = self.max_damage(1) / 2;
+			max_damage = crit -> 8 * crit + self.bonus;
+			len = 4;
+			name = "not the name";
+		}
+		struct Spell { level: int }
+		impl Spell {
+			save_dc = 8 + self.level + spell_mod;
+			upcast = by -> Spell { level: self.level + by };
+		}
+		spell_mod = 3;
+		axe = Weapon { name: "Axe", bonus: 2 };
+		axe_attack = axe.attack(5);
+		fireball_dc = Spell { level: 3 }.save_dc();
+		"#,
+				"",
+			)
+			.unwrap();
+		runtime
+	}
+
+	#[test]
+	fn valid_structs() {
+		let runtime = runtime();
+		let value = runtime
+			.run_val(
+				r#"Character {
+					name: "Bob",
+					level: 3,
+					items: [sword, Item { name: "Rope", weight: 0.5 }],
+					stats: [[1, 2], []],
+					notes: 1d4
+				}"#,
+			)
+			.unwrap();
+		match value {
+			Value::Struct(s) => {
+				assert_eq!(s.kind.0, "Character");
+				assert_eq!(s.values["level"], Value::Integer(3));
+			}
+			_ => panic!("expected a struct, got {:?}", value),
+		}
+	}
+
+	#[test]
+	fn defaults() {
+		let runtime = runtime();
+		let field = |src: &str, field: &str| match runtime.run_val(src).unwrap() {
+			Value::Struct(s) => s.values[field].clone(),
+			v => panic!("expected a struct, got {:?}", v),
+		};
+		for (score, modifier) in [(15, 2), (14, 2), (9, -1), (8, -1), (3, -4)] {
+			let src = format!("Ability {{ score: {} }}", score);
+			assert!(
+				matches!(field(&src, "mod"), Value::Integer(m) if m == modifier),
+				"{}",
+				src
+			);
+		}
+		assert_eq!(
+			field("Ability { score: 8, mod: 5 }", "mod"),
+			Value::Integer(5)
+		);
+		assert_eq!(
+			field(r#"Weapon { name: "Axe" }"#, "bonus"),
+			Value::Integer(0)
+		);
+		assert!(matches!(
+			field(r#"Weapon { name: "Axe", bonus: 2 }"#, "damage"),
+			Value::Integer(3..=10)
+		));
+		assert!(matches!(
+			field(r#"Weapon { name: "Axe" }"#, "describe"),
+			Value::Function(_)
+		));
+		// functions in fields can use the other fields of the instance
+		assert_eq!(
+			runtime
+				.run_val(r#"Weapon { name: "Axe" }.describe(2)"#)
+				.unwrap(),
+			Value::String("Axe x2".to_string())
+		);
+		assert_eq!(
+			runtime.run_val("axe.describe(3)").unwrap(),
+			Value::String("Axe x3".to_string())
+		);
+	}
+
+	#[test]
+	fn struct_access() {
+		let runtime = runtime();
+		assert_eq!(
+			runtime.run_val("sword.name").unwrap(),
+			Value::String("Sword".to_string())
+		);
+		assert_eq!(
+			runtime.run_val(r#"sword["weight"] + 1"#).unwrap(),
+			Value::Integer(4)
+		);
+		assert_eq!(
+			runtime.run_val("Ability { score: 12 }.mod").unwrap(),
+			Value::Integer(1)
+		);
+		assert!(matches!(
+			runtime.run_val("sword.damage"),
+			Err(RuntimeError::NoPropertyOnKind(ValueKind::Struct(name), _)) if name == "Item"
+		));
+		assert_eq!(
+			runtime
+				.run_val(r#"sword == Item { weight: 3, name: "Sword" }"#)
+				.unwrap(),
+			Value::Boolean(true)
+		);
+		assert_eq!(
+			runtime
+				.run_val(r#"sword == Item { weight: 4, name: "Sword" }"#)
+				.unwrap(),
+			Value::Boolean(false)
+		);
+	}
+
+	#[test]
+	fn methods() {
+		let runtime = runtime();
+		assert!(matches!(
+			runtime.run_val("axe.attack(5)").unwrap(),
+			Value::Integer(8..=27)
+		));
+		assert_eq!(
+			runtime.run_val("axe.average()").unwrap(),
+			Value::Decimal(5.0)
+		);
+		assert_eq!(
+			runtime.run_val("Spell { level: 2 }.save_dc()").unwrap(),
+			Value::Integer(13)
+		);
+		assert_eq!(
+			runtime
+				.run_val("Spell { level: 1 }.upcast(2).save_dc()")
+				.unwrap(),
+			Value::Integer(14)
+		);
+		// fields come before methods, and methods before standard functions
+		assert_eq!(
+			runtime.run_val("axe.name").unwrap(),
+			Value::String("Axe".to_string())
+		);
+		assert_eq!(runtime.run_val("axe.len()").unwrap(), Value::Integer(4));
+		assert!(runtime.run_val("axe.average").is_err());
+		assert!(runtime.run_val("sword.attack(1)").is_err());
+
+		// method bodies are part of the definitions of variables that call them
+		assert!(runtime.graph.is_pure("fireball_dc"));
+		assert!(runtime
+			.graph
+			.dependencies_of("fireball_dc")
+			.contains("spell_mod"));
+		assert!(!runtime.graph.is_pure("axe_attack"));
+	}
+
+	#[test]
+	fn inheritance() {
+		let mut runtime = Runtime::new(rand::thread_rng(), ());
+		runtime
+			.load(
+				r#"
+		struct Character { name: string, level: int, hit_die: 8, hp: hit_die * level }
+		struct Caster extends Character { spell_mod: int }
+		struct Wizard extends Caster { spellbook: string[], hit_die: 6 }
+		struct Party { leader: Character, casters: Caster[] }
+		impl Character { describe = "level " + self.level + " " + self.name; }
+		impl Caster { save_dc = 8 + self.spell_mod; }
+		impl Wizard { describe = "wizard " + self.name; }
+
+		merlin = Wizard { name: "Merlin", level: 2, spell_mod: 4, spellbook: [] };
+		conan = Character { name: "Conan", level: 3 };
+		"#,
+				"",
+			)
+			.unwrap();
+		let merlin = runtime.run_val("merlin").unwrap();
+		assert!(matches!(&merlin, Value::Struct(s) if s.values.len() == 6));
+		// defaults can be overridden, and inherited defaults use the new value
+		assert_eq!(runtime.run_val("merlin.hp").unwrap(), Value::Integer(12));
+		assert_eq!(runtime.run_val("conan.hp").unwrap(), Value::Integer(24));
+		// methods are inherited and can be overridden
+		assert_eq!(
+			runtime.run_val("merlin.save_dc()").unwrap(),
+			Value::Integer(12)
+		);
+		assert_eq!(
+			runtime.run_val("merlin.describe()").unwrap(),
+			Value::String("wizard Merlin".to_string())
+		);
+		assert!(runtime.run_val("conan.save_dc()").is_err());
+
+		// subtypes are accepted where a supertype is expected
+		runtime
+			.run_val("Party { leader: merlin, casters: [merlin] }")
+			.unwrap();
+		assert!(matches!(
+			runtime.run_val("Party { leader: conan, casters: [conan] }"),
+			Err(RuntimeError::InvalidStruct(_, errors)) if errors.len() == 1
+		));
+		assert!(matches!(
+			runtime.run_val(r#"Wizard { name: "Bad", level: 1, spellbook: [] }"#),
+			Err(RuntimeError::InvalidStruct(_, errors)) if errors.len() == 1
+		));
+	}
+
+	#[test]
+	fn bad_inheritance() {
+		let load = |src: &str| Runtime::new(rand::thread_rng(), ()).load(src, "");
+		assert!(matches!(
+			load("struct A extends B { a: int }"),
+			Err(RuntimeError::StructNotFound(name)) if name == "B"
+		));
+		assert!(matches!(
+			load("struct A extends B { a: int }\nstruct B extends A { b: int }"),
+			Err(RuntimeError::InheritanceCycle(chain)) if chain == vec!["A", "B", "A"]
+		));
+	}
+
+	#[test]
+	fn struct_display() {
+		colored::control::set_override(false);
+		let runtime = runtime();
+		assert_eq!(
+			format!("{}", runtime.run_val("sword").unwrap()),
+			r#"Item { name: "Sword", weight: 3 }"#
+		);
+		assert_eq!(runtime.run_val("sword").unwrap().kind().to_string(), "Item");
+	}
+
+	#[test]
+	fn invalid_structs() {
+		let runtime = runtime();
+		let err = runtime
+			.run_val(
+				r#"Character {
+					name: 5,
+					items: [sword, 10],
+					stats: [["not an int"]],
+					notes: 0,
+					age: 30
+				}"#,
+			)
+			.unwrap_err();
+		match err {
+			RuntimeError::InvalidStruct(name, mut errors) => {
+				assert_eq!(name, "Character");
+				errors.sort_by_key(|e| e.to_string());
+				assert!(matches!(
+					errors.as_slice(),
+					[
+						FieldError::NotOnStruct(age),
+						FieldError::WrongType(items, _, ValueKind::Array),
+						FieldError::WrongType(name, _, ValueKind::Integer),
+						FieldError::WrongType(stats, _, ValueKind::Array),
+						FieldError::Missing(level, _),
+					] if age == "age" && items == "items" && name == "name"
+						&& stats == "stats" && level == "level"
+				));
+			}
+			err => panic!("expected InvalidStruct, got {:?}", err),
+		}
 	}
 }
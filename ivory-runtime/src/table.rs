@@ -0,0 +1,232 @@
+use ivory_tokenizer::{accessor::AccessorComponent, table::TableRoll};
+
+use crate::{
+	roll::{Draw, Roll},
+	runtime::Runtime,
+	value::Value,
+	Result, RuntimeError,
+};
+
+/// Checks that the rows of a rolled table are for every roll of its die,
+/// each roll having exactly one row.
+pub fn check_ranges(name: &str, roll: &TableRoll) -> Result<()> {
+	let mut ranges = roll.ranges.clone();
+	for range in &ranges {
+		if range.start == 0 || range.start > range.end || range.end > roll.die {
+			return Err(RuntimeError::TableRangeOutside(
+				name.to_string(),
+				range.to_string(),
+				roll.die,
+			));
+		}
+	}
+	ranges.sort_by_key(|range| range.start);
+	let mut next = 1;
+	for range in ranges {
+		if range.start > next {
+			return Err(RuntimeError::TableGap(
+				name.to_string(),
+				next,
+				range.start - 1,
+			));
+		}
+		if range.start < next {
+			return Err(RuntimeError::TableOverlap(name.to_string(), range.start));
+		}
+		next = range.end + 1;
+	}
+	if next <= roll.die {
+		return Err(RuntimeError::TableGap(name.to_string(), next, roll.die));
+	}
+	Ok(())
+}
+
+/// Whether the rest of an accessor on a rolled table rolls on it, like
+/// `wild_magic.roll()`
+pub fn is_roll(components: &[AccessorComponent]) -> bool {
+	matches!(
+		components,
+		[AccessorComponent::Property(prop), AccessorComponent::Call(args), ..]
+			if prop.0 == "roll" && args.is_empty()
+	)
+}
+
+/// Rolls a table's die, giving the row for that roll. Rows that aren't
+/// structs have the roll in them as `roll`.
+pub fn roll(
+	runtime: &Runtime,
+	name: &str,
+	roll: &TableRoll,
+	rows: Value,
+) -> Result<Value> {
+	let die = Roll::die(runtime, roll.die);
+	let rolled = die.value();
+	let mut row = roll
+		.ranges
+		.iter()
+		.position(|range| range.contains(rolled))
+		.and_then(|i| match rows {
+			Value::Array(mut rows) if i < rows.len() => Some(rows.swap_remove(i)),
+			_ => None,
+		})
+		.ok_or_else(|| RuntimeError::TableGap(name.to_string(), rolled, rolled))?;
+	if let Value::Object(fields) = &mut row {
+		fields.insert("roll".to_string(), Value::Roll(die.clone()));
+	}
+	runtime.draws.borrow_mut().push(Draw {
+		name: format!("{}.roll", name),
+		rolls: vec![die],
+		value: row.clone(),
+	});
+	Ok(row)
+}
+
+#[cfg(test)]
+mod test {
+	use crate::{check::Checker, runtime::Runtime, value::Value, RuntimeError};
+	use ivory_tokenizer::{istruct::StructName, itype::Type};
+
+	const SRC: &str = r#"
+surge_dc = 12;
+
+table wild_magic d20
+| roll  | effect      | dc       |
+| 1-5   | "Goblins"   | 10       |
+| 6     | "Fireball"  | surge_dc |
+| 07-20 | "Nothing"   | 0        |
+
+surge = wild_magic.roll();
+rows = wild_magic.len();
+"#;
+
+	fn load(src: &str) -> crate::Result<Runtime> {
+		let mut runtime = Runtime::new(rand::thread_rng(), ());
+		runtime.load(src, "")?;
+		Ok(runtime)
+	}
+
+	#[test]
+	fn rolled_tables() {
+		let runtime = load(SRC).unwrap();
+		for _ in 0..30 {
+			let row = match runtime.run_val("wild_magic.roll()").unwrap() {
+				Value::Object(row) => row,
+				other => panic!("Expected a row, got {}", other),
+			};
+			let rolled = row["roll"].to_integer().unwrap();
+			let effect = match rolled {
+				1..=5 => "Goblins",
+				6 => "Fireball",
+				_ => "Nothing",
+			};
+			assert_eq!(row["effect"], Value::String(effect.to_string()));
+			let draws = runtime.draws.borrow();
+			assert_eq!(draws.len(), 1);
+			assert_eq!(draws[0].name, "wild_magic.roll");
+			assert_eq!(draws[0].rolls[0].sides, 20);
+		}
+		// the rows are still an array, holding their ranges
+		assert_eq!(runtime.run_val("rows").unwrap(), Value::Integer(3));
+		assert_eq!(
+			runtime.run_val("wild_magic[1].dc").unwrap(),
+			Value::Integer(12)
+		);
+		assert_eq!(
+			runtime.run_val("wild_magic[2].roll").unwrap(),
+			Value::Array(vec![Value::Integer(7), Value::Integer(20)])
+		);
+		assert!(!runtime.graph.is_pure("surge"));
+		assert!(runtime.graph.is_pure("rows"));
+		assert!(runtime.run_val("wild_magic.roll").is_err());
+
+		let checker = Checker::new(&runtime);
+		assert_eq!(checker.variable("surge"), Type::Object);
+		assert!(runtime.check().is_empty());
+	}
+
+	#[test]
+	fn typed_tables() {
+		let runtime = load(
+			r#"
+struct Spell {
+	name: string,
+	level: int
+}
+
+table spells: Spell
+| name       | level |
+|:-----------|------:|
+| "Fireball" | 3     |
+| "Shield"   | 1     |
+
+table surges: Spell d4
+| roll | name       | level |
+| 1-3  | "Nothing"  | 0     |
+| 4    | "Fireball" | 3     |
+
+table broken: Spell
+| name   | level  |
+| "Oops" | "high" |
+"#,
+		)
+		.unwrap();
+		assert_eq!(
+			runtime.run_val("spells[0].level").unwrap(),
+			Value::Integer(3)
+		);
+		assert_eq!(
+			runtime.run_val("spells.map(s -> s.name)").unwrap(),
+			Value::Array(vec![
+				Value::String("Fireball".to_string()),
+				Value::String("Shield".to_string())
+			])
+		);
+		assert!(matches!(
+			runtime.run_val("surges.roll()").unwrap(),
+			Value::Struct(s) if s.kind.0 == "Spell"
+		));
+		assert!(runtime.run_val("broken").is_err());
+
+		let checker = Checker::new(&runtime);
+		let spells =
+			Type::Array(Box::new(Type::Struct(StructName("Spell".to_string()))));
+		assert_eq!(checker.variable("spells"), spells);
+		let problems = runtime.check();
+		assert_eq!(problems.len(), 1);
+		assert_eq!(problems[0].location, "broken");
+	}
+
+	#[test]
+	fn table_ranges() {
+		let table = |rows: &str| format!("table t d6\n| roll | a |\n{}\n", rows);
+		assert!(matches!(
+			load(&table("| 1-2 | 1 |\n| 4-6 | 2 |")),
+			Err(RuntimeError::TableGap(_, 3, 3))
+		));
+		assert!(matches!(
+			load(&table("| 1-4 | 1 |\n| 4-6 | 2 |")),
+			Err(RuntimeError::TableOverlap(_, 4))
+		));
+		assert!(matches!(
+			load(&table("| 1-3 | 1 |")),
+			Err(RuntimeError::TableGap(_, 4, 6))
+		));
+		assert!(matches!(
+			load(&table("| 1-3 | 1 |\n| 4-7 | 2 |")),
+			Err(RuntimeError::TableRangeOutside(..))
+		));
+		assert!(matches!(
+			load(&table("| 3-1 | 1 |\n| 4-6 | 2 |")),
+			Err(RuntimeError::TableRangeOutside(..))
+		));
+		// rows can be in any order
+		assert!(load(&table("| 4-6 | 2 |\n| 1-3 | 1 |")).is_ok());
+		// 00 is the highest roll of a d100
+		let runtime =
+			load("table t d100\n| roll | a |\n| 01-99 | 1 |\n| 00 | 2 |\n").unwrap();
+		assert_eq!(
+			runtime.run_val("t[1].roll").unwrap(),
+			Value::Array(vec![Value::Integer(100), Value::Integer(100)])
+		);
+	}
+}
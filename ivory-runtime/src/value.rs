@@ -7,18 +7,22 @@ use ivory_tokenizer::{
 	itype::Type,
 	values::{
 		array::ArrayValue, boolean::BooleanValue, decimal::DecimalValue,
-		function::FunctionValue, integer::IntegerValue, object::ObjectValue,
-		string::StringValue,
+		function::FunctionValue, integer::IntegerValue, none::NoneValue,
+		object::ObjectValue, string::StringValue,
 	},
 };
 
+use crate::{
+	enum_value::{run_match, EnumValue},
+	struct_value::StructValue,
+	Result,
+};
 use crate::{
 	error::RuntimeError,
 	expr::RolledOp,
 	roll::Roll,
-	runtime::{Runtime, RuntimeContext},
+	runtime::{Runtime, RuntimeContext, RuntimeValues},
 };
-use crate::{struct_value::StructValue, Result};
 
 use std::{collections::HashMap, fmt::Display};
 
@@ -30,6 +34,10 @@ static K_ROLL: &'static str = "roll";
 static K_ARRAY: &'static str = "array";
 static K_OBJECT: &'static str = "object";
 static K_FUNCTION: &'static str = "function";
+static K_STRUCT: &'static str = "struct";
+static K_ENUM: &'static str = "enum";
+static K_NAMESPACE: &'static str = "namespace";
+static K_NONE: &'static str = "none";
 
 #[derive(Clone, Debug)]
 pub enum Value {
@@ -42,6 +50,11 @@ pub enum Value {
 	Object(HashMap<String, Value>),
 	Function(FunctionValue),
 	Struct(StructValue),
+	Enum(EnumValue),
+	/// The `math` namespace, which can be stored and passed around like any
+	/// other value
+	Math,
+	None,
 }
 
 impl Value {
@@ -52,12 +65,29 @@ impl Value {
 			Value::Boolean(_) => Type::Boolean,
 			Value::String(_) => Type::String,
 			Value::Roll(_) => Type::Roll,
-			Value::Array(vals) => Type::Array({
-				todo!();
-			}),
+			Value::Array(vals) => {
+				// arrays with more than one type of value in them are any[]
+				let mut types = vals.iter().map(|v| v.val_type());
+				let first = types.next().unwrap_or(Type::Any);
+				if types.all(|t| t == first) {
+					Type::Array(Box::new(first))
+				} else {
+					Type::Array(Box::new(Type::Any))
+				}
+			}
 			Value::Object(_) => Type::Object,
-			Value::Function(_) => todo!(),
-			Value::Struct(_) => todo!(),
+			Value::Function(f) => Type::Function(
+				f.arg_types
+					.iter()
+					.map(|t| t.clone().unwrap_or(Type::Any))
+					.collect(),
+				Box::new(f.ret.clone().unwrap_or(Type::Any)),
+			),
+			Value::Struct(s) => Type::Struct(s.kind.clone()),
+			Value::Enum(e) => Type::Enum(e.kind.clone()),
+			// used like an object of functions and constants
+			Value::Math => Type::Object,
+			Value::None => Type::Optional(Box::new(Type::Any)),
 		}
 	}
 
@@ -72,7 +102,10 @@ impl Value {
 			Value::Array(_) => ValueKind::Array,
 			Value::Object(_) => ValueKind::Object,
 			Value::Function(_) => ValueKind::Function,
-			Value::Struct(_) => ValueKind::Object,
+			Value::Struct(s) => ValueKind::Struct(s.kind.0.clone()),
+			Value::Enum(e) => ValueKind::Enum(e.kind.0.clone()),
+			Value::Math => ValueKind::Namespace,
+			Value::None => ValueKind::None,
 		}
 	}
 
@@ -83,7 +116,7 @@ impl Value {
 				if let Some(c) = s.chars().nth(i) {
 					Ok(Value::String(c.to_string()))
 				} else {
-					Err(RuntimeError::IndexOutOfBounds(i, s.len()))
+					Err(RuntimeError::IndexOutOfBounds(i, s.chars().count()))
 				}
 			}
 			Value::Roll(r) => {
@@ -110,6 +143,14 @@ impl Value {
 					Err(RuntimeError::PropNotFound(i.clone()))
 				}
 			}
+			Value::Struct(s) => {
+				let i = i.to_string()?;
+				if let Some(v) = s.values.get(&i) {
+					Ok(v.clone())
+				} else {
+					Err(RuntimeError::NoPropertyOnKind(self.kind(), i.clone()))
+				}
+			}
 			_ => Err(RuntimeError::CannotIndexKind(self.kind())),
 		}
 	}
@@ -131,7 +172,21 @@ impl Value {
 				}
 			}
 			RolledOp::Comparator(Comparator::Eq) => Ok(Boolean(self == rhs)),
+			RolledOp::Coalesce => Ok(match self {
+				None => rhs.clone(),
+				_ => self.clone(),
+			}),
 			op => match (self, rhs) {
+				(String(_), Integer(_) | Decimal(_) | Boolean(_) | Roll(_))
+				| (Integer(_) | Decimal(_) | Boolean(_) | Roll(_), String(_))
+					if runtime.strict =>
+				{
+					Err(RuntimeError::StrictCoercion(
+						self.kind(),
+						op.clone(),
+						rhs.kind(),
+					))
+				}
 				(Integer(a), Integer(b)) => a.op(b, op, runtime, ctx),
 				(Integer(a), Decimal(b)) => (*a as f32).op(b, op, runtime, ctx),
 				(Integer(a), Boolean(b)) => a.op(&(*b as i32), op, runtime, ctx),
@@ -220,7 +275,10 @@ impl Value {
 			Value::Array(a) => Ok(a.len() > 0),
 			Value::Object(o) => Ok(o.len() > 0),
 			Value::Function(_) => Ok(true),
+			Value::None => Ok(false),
 			Value::Struct(_) => Ok(true),
+			Value::Enum(_) => Ok(true),
+			Value::Math => Ok(true),
 		}
 	}
 
@@ -406,14 +464,48 @@ impl Value {
 					})
 					.collect::<Result<HashMap<String, Value>>>()?,
 			),
-			ivory_tokenizer::values::Value::Struct(s) => todo!(),
+			ivory_tokenizer::values::Value::Struct(s) => {
+				Value::Struct(StructValue::build(runtime, ctx, s)?)
+			}
 			ivory_tokenizer::values::Value::Function(f) => Value::Function(f.clone()),
+			ivory_tokenizer::values::Value::Match(m) => run_match(runtime, ctx, m)?,
+			ivory_tokenizer::values::Value::None(NoneValue) => Value::None,
 		})
 	}
 
+	/// Returns true if this value can be used where a value of type `t` is
+	/// expected. Structs can be used where a struct they extend is expected.
+	pub fn is_type(&self, t: &Type, values: &RuntimeValues) -> bool {
+		match (t, self) {
+			(Type::Any, _) => true,
+			(Type::Optional(_), Value::None) => true,
+			(Type::Optional(t), value) => value.is_type(t, values),
+			(Type::Integer, Value::Integer(_)) => true,
+			(Type::Decimal, Value::Decimal(_) | Value::Integer(_)) => true,
+			(Type::Boolean, Value::Boolean(_)) => true,
+			(Type::String, Value::String(_)) => true,
+			(Type::Roll, Value::Roll(_)) => true,
+			(Type::Object, Value::Object(_)) => true,
+			(Type::Array(t), Value::Array(vals)) => {
+				vals.iter().all(|v| v.is_type(t, values))
+			}
+			(Type::Struct(name), Value::Struct(s)) => {
+				values.is_subtype(&s.kind.0, &name.0)
+			}
+			// named types are parsed as structs, so they can also be enums
+			(Type::Enum(name) | Type::Struct(name), Value::Enum(e)) => {
+				e.kind == *name
+			}
+			(Type::Function(..), Value::Function(_)) => {
+				type_fits(&self.val_type(), t, values)
+			}
+			_ => false,
+		}
+	}
+
 	/// Returns true if values have the same type
 	pub fn eq_type(&self, other: &Value) -> bool {
-		todo!();
+		self.val_type() == other.val_type()
 	}
 }
 
@@ -428,15 +520,49 @@ impl PartialEq for Value {
 			(Roll(l0), Roll(r0)) => l0 == r0,
 			(Array(l0), Array(r0)) => l0 == r0,
 			(Object(l0), Object(r0)) => l0 == r0,
+			(Struct(l0), Struct(r0)) => l0 == r0,
+			(Enum(l0), Enum(r0)) => l0 == r0,
 			(Function(_), Function(_)) => false,
+			(Math, Math) => true,
+			(None, None) => true,
 			(Decimal(a), Integer(b)) | (Integer(b), Decimal(a)) => *b as f32 == *a,
 			_ => false,
 		}
 	}
 }
 
+/// Returns true if a value of type `t` can be used where a value of type
+/// `expected` is expected
+pub fn type_fits(t: &Type, expected: &Type, values: &RuntimeValues) -> bool {
+	match (t, expected) {
+		(Type::Any, _) | (_, Type::Any) => true,
+		(Type::Integer, Type::Decimal) => true,
+		(Type::Optional(t), Type::Optional(expected)) => {
+			type_fits(t, expected, values)
+		}
+		(t, Type::Optional(expected)) => type_fits(t, expected, values),
+		(Type::Array(t), Type::Array(expected)) => type_fits(t, expected, values),
+		(Type::Struct(name), Type::Struct(expected)) => {
+			values.is_subtype(&name.0, &expected.0)
+		}
+		(Type::Enum(name), Type::Enum(expected) | Type::Struct(expected))
+		| (Type::Struct(name), Type::Enum(expected)) => name == expected,
+		// a function fits if it takes every argument the expected function would
+		// be given, and returns something the expected function could
+		(Type::Function(params, ret), Type::Function(e_params, e_ret)) => {
+			params.len() == e_params.len()
+				&& params
+					.iter()
+					.zip(e_params)
+					.all(|(p, e_p)| type_fits(e_p, p, values))
+				&& type_fits(ret, e_ret, values)
+		}
+		(t, expected) => t == expected,
+	}
+}
+
 fn same_op_err(kind: ValueKind, op: &RolledOp) -> Result<Value> {
-	Err(RuntimeError::CannotRunOp(kind, op.clone(), kind))
+	Err(RuntimeError::CannotRunOp(kind.clone(), op.clone(), kind))
 }
 
 fn append(op: &RolledOp, a: &Vec<Value>, b: &Value) -> Result<Value> {
@@ -516,14 +642,17 @@ impl RunOp for i32 {
 				ExprOpMathKind::Add => Value::Integer(self + other),
 				ExprOpMathKind::Sub => Value::Integer(self - other),
 				ExprOpMathKind::Mul => Value::Integer(self * other),
-				ExprOpMathKind::Div => Value::Decimal(match &round {
-					Some(round) => match round {
-						ExprOpMathRound::Up => (self + (other - 1)) as f32 / *other as f32,
-						ExprOpMathRound::Down => *self as f32 / *other as f32,
-						ExprOpMathRound::Round => (*self as f32 / *other as f32).round(),
-					},
-					None => *self as f32 / *other as f32,
-				}),
+				ExprOpMathKind::Div => {
+					// rounding gives back an integer, rounded the same way as
+					// decimals are, including below zero
+					let res = *self as f64 / *other as f64;
+					match &round {
+						Some(ExprOpMathRound::Up) => Value::Integer(res.ceil() as i32),
+						Some(ExprOpMathRound::Down) => Value::Integer(res.floor() as i32),
+						Some(ExprOpMathRound::Round) => Value::Integer(res.round() as i32),
+						None => Value::Decimal(res as f32),
+					}
+				}
 			}),
 			RolledOp::Comparator(c) => Ok(Value::Boolean(match c {
 				Comparator::Gt => *self > *other,
@@ -610,7 +739,7 @@ impl RunOp for Vec<Value> {
 	}
 }
 
-#[derive(Clone, Debug, Copy)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum ValueKind {
 	Integer,
 	Decimal,
@@ -620,6 +749,12 @@ pub enum ValueKind {
 	Array,
 	Object,
 	Function,
+	/// A struct, with the name of its definition
+	Struct(String),
+	/// An enum, with the name of its definition
+	Enum(String),
+	Namespace,
+	None,
 }
 
 impl ValueKind {
@@ -633,13 +768,20 @@ impl ValueKind {
 			ValueKind::Array => K_ARRAY,
 			ValueKind::Object => K_OBJECT,
 			ValueKind::Function => K_FUNCTION,
+			ValueKind::Struct(_) => K_STRUCT,
+			ValueKind::Enum(_) => K_ENUM,
+			ValueKind::Namespace => K_NAMESPACE,
+			ValueKind::None => K_NONE,
 		}
 	}
 }
 
 impl Display for ValueKind {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		write!(f, "{}", self.to_str())
+		match self {
+			ValueKind::Struct(name) | ValueKind::Enum(name) => write!(f, "{}", name),
+			kind => write!(f, "{}", kind.to_str()),
+		}
 	}
 }
 
@@ -689,6 +831,15 @@ impl Display for Value {
 			Value::Struct(s) => {
 				write!(f, "{}", s)
 			}
+			Value::Enum(e) => {
+				write!(f, "{}", e)
+			}
+			Value::Math => {
+				write!(f, "<math>")
+			}
+			Value::None => {
+				write!(f, "{}", "none".cyan())
+			}
 		}
 	}
 }
@@ -3,6 +3,7 @@ use std::fmt::Display;
 use ivory_expression::Expression;
 use nom::{
 	branch::alt,
+	bytes::complete::tag,
 	character::complete::char,
 	combinator::map,
 	multi::{many0, separated_list0},
@@ -17,13 +18,13 @@ use crate::{
 	Parse,
 };
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum AccessorRoot {
 	Variable(VariableName),
 	Value(Value),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Accessor(pub AccessorRoot, pub Vec<AccessorComponent>);
 
 impl Parse for Accessor {
@@ -58,9 +59,11 @@ impl Display for Accessor {
 	}
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum AccessorComponent {
 	Property(VariableName),
+	/// `?.name`, which gives `none` instead of an error when accessed on `none`
+	OptionalProperty(VariableName),
 	Index(Expression<Op, ExpressionToken>),
 	Call(Vec<Expression<Op, ExpressionToken>>),
 }
@@ -71,6 +74,10 @@ impl Parse for AccessorComponent {
 			map(preceded(pair(char('.'), ws0), VariableName::parse), |res| {
 				AccessorComponent::Property(res)
 			});
+		let optional_property = map(
+			preceded(pair(tag("?."), ws0), VariableName::parse),
+			AccessorComponent::OptionalProperty,
+		);
 		let index = map(
 			delimited(
 				pair(char('['), ws0),
@@ -91,7 +98,7 @@ impl Parse for AccessorComponent {
 			|vals| AccessorComponent::Call(vals),
 		);
 
-		alt((property, index, call))(input)
+		alt((property, optional_property, index, call))(input)
 	}
 }
 
@@ -99,6 +106,7 @@ impl Display for AccessorComponent {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		match self {
 			AccessorComponent::Property(p) => write!(f, ".{}", p),
+			AccessorComponent::OptionalProperty(p) => write!(f, "?.{}", p),
 			AccessorComponent::Index(i) => write!(f, "[{}]", i),
 			AccessorComponent::Call(c) => {
 				write!(f, "({})", comma_separated_display(c))
@@ -116,6 +124,7 @@ fn parse_accessor() {
 		"foobar()",
 		"fizzlord(123,\n456\n,biggler)",
 		"math.square_root(5 * 5)",
+		"character.shield?.ac",
 	];
 	for v in vs {
 		match Accessor::parse(v) {
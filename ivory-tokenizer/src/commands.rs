@@ -9,8 +9,13 @@ use nom::{
 };
 
 use crate::{
-	istruct::StructDefinition, module::iuse::Use, table::Table, util::ws0,
-	variable::Variable, Parse,
+	ienum::EnumDefinition,
+	istruct::{StructDefinition, StructImpl},
+	module::iuse::Use,
+	table::Table,
+	util::ws0,
+	variable::Variable,
+	Parse,
 };
 
 #[derive(Clone, Debug)]
@@ -18,6 +23,9 @@ pub enum Command {
 	Use(Use),
 	Variable(Variable),
 	StructDefinition(StructDefinition),
+	StructImpl(StructImpl),
+	EnumDefinition(EnumDefinition),
+	Table(Table),
 }
 
 impl Parse for Command {
@@ -33,7 +41,15 @@ impl Parse for Command {
 				pair(space0, alt((line_ending, eof))),
 			),
 			terminated(
-				map(Table::parse, |t| Self::Variable(t.into_variable())),
+				map(EnumDefinition::parse, Self::EnumDefinition),
+				pair(space0, alt((line_ending, eof))),
+			),
+			terminated(
+				map(StructImpl::parse, Self::StructImpl),
+				pair(space0, alt((line_ending, eof))),
+			),
+			terminated(
+				map(Table::parse, Self::Table),
 				pair(space0, alt((line_ending, eof))),
 			),
 		))(input)
@@ -45,7 +61,10 @@ impl Display for Command {
 		match self {
 			Command::Variable(v) => write!(f, "{};", v),
 			Command::StructDefinition(d) => write!(f, "{}", d),
+			Command::StructImpl(i) => write!(f, "{}", i),
+			Command::EnumDefinition(e) => write!(f, "{}", e),
 			Command::Use(u) => write!(f, "{};", u),
+			Command::Table(t) => write!(f, "{}", t),
 		}
 	}
 }
@@ -57,5 +76,8 @@ fn parse_command() {
 		"x = y + z;",
 		"use * from \"http://fakewebsite.biz/source.ivory\";",
 		"struct Foo { array_2d: int[][] }",
+		"impl Foo { double = self.bar * 2; }",
+		"enum Size { Small, Medium, Large }",
+		"table loot d6\n| roll | item |\n| 1-5 | \"rope\" |\n| 6 | \"gem\" |",
 	]);
 }
@@ -62,18 +62,21 @@ impl<O: Parse, T: Parse> Parse for Expression<O, T> {
 	}
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Op {
 	Dice,
 	Math(ExprOpMath),
 	DiceOp(DiceOp),
 	Comparator(Comparator),
 	Logic(LogicOp),
+	/// `a ?? b` is `b` when `a` is none
+	Coalesce,
 }
 
 impl Parse for Op {
 	fn parse(input: &str) -> nom::IResult<&str, Self> {
 		alt((
+			value(Op::Coalesce, tag("??")),
 			value(Op::Dice, tag("d")),
 			map(ExprOpMath::parse, |m| Op::Math(m)),
 			map(DiceOp::parse, |d| Op::DiceOp(d)),
@@ -91,11 +94,12 @@ impl Display for Op {
 			Op::DiceOp(op) => write!(f, "{}", op),
 			Op::Comparator(op) => write!(f, "{}", op),
 			Op::Logic(op) => write!(f, "{}", op),
+			Op::Coalesce => write!(f, "??"),
 		}
 	}
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct ExpressionToken(pub Accessor);
 
 impl ExpressionToken {
@@ -131,5 +135,7 @@ fn parse_expression() {
 		"18 + bogos[34] / binted[8 * 8]",
 		"((((((((((((69))))))))))))",
 		"1d20 r<= 5",
+		"shield?.ac ?? 0",
+		"a ?? b ? c : d",
 	]);
 }
@@ -12,13 +12,13 @@ use crate::Parse;
 
 use super::logic::Comparator;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct DiceOp {
 	pub op: DiceOpCmp,
 	pub cmp: Comparator,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum DiceOpCmp {
 	Keep,
 	Reroll,
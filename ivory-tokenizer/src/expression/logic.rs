@@ -4,7 +4,7 @@ use nom::{branch::alt, bytes::complete::tag, combinator::value};
 
 use crate::Parse;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Comparator {
 	Gt,
 	Lt,
@@ -41,7 +41,7 @@ impl Display for Comparator {
 	}
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum LogicOp {
 	And,
 	Or,
@@ -14,7 +14,7 @@ use crate::{util::ws0, Parse};
 
 use super::{ExpressionToken, Op};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum ExprOpMath {
 	Binary {
 		kind: ExprOpMathKind,
@@ -38,7 +38,7 @@ impl Parse for ExprOpMath {
 	}
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum ExprOpMathKind {
 	Add,
 	Sub,
@@ -78,7 +78,7 @@ impl Display for ExprOpMathKind {
 	}
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum ExprOpMathRound {
 	Up,
 	Down,
@@ -0,0 +1,126 @@
+use std::fmt::Display;
+
+use nom::{
+	bytes::complete::tag,
+	combinator::{map, opt},
+	multi::separated_list0,
+	sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
+};
+
+use crate::{
+	istruct::StructName,
+	itype::Type,
+	util::{comma_separated_display, ws0, ws1},
+	Parse,
+};
+
+/// A closed set of values, like `enum DamageType { Fire, Cold }`. Variants
+/// can hold values, like `enum Shape { Circle(decimal), Square(int) }`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnumDefinition {
+	pub name: StructName,
+	pub variants: Vec<EnumVariant>,
+}
+
+impl EnumDefinition {
+	pub fn variant(&self, name: &str) -> Option<&EnumVariant> {
+		self.variants.iter().find(|v| v.name.0 == name)
+	}
+}
+
+impl Parse for EnumDefinition {
+	fn parse(input: &str) -> nom::IResult<&str, Self> {
+		let name = preceded(pair(tag("enum"), ws1), StructName::parse);
+		map(
+			separated_pair(
+				name,
+				ws0,
+				delimited(
+					pair(tag("{"), ws0),
+					terminated(
+						separated_list0(tuple((ws0, tag(","), ws0)), EnumVariant::parse),
+						opt(pair(ws0, tag(","))),
+					),
+					pair(ws0, tag("}")),
+				),
+			),
+			|(name, variants)| Self { name, variants },
+		)(input)
+	}
+}
+
+impl Display for EnumDefinition {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"enum {} {{\n{}}}",
+			self.name,
+			self
+				.variants
+				.iter()
+				.fold(String::new(), |s, v| format!("{}\t{},\n", s, v))
+		)
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnumVariant {
+	pub name: StructName,
+	/// The types of the values the variant holds
+	pub payload: Vec<Type>,
+}
+
+impl Parse for EnumVariant {
+	fn parse(input: &str) -> nom::IResult<&str, Self> {
+		map(
+			pair(
+				StructName::parse,
+				opt(delimited(
+					pair(tag("("), ws0),
+					separated_list0(tuple((ws0, tag(","), ws0)), Type::parse),
+					pair(ws0, tag(")")),
+				)),
+			),
+			|(name, payload)| Self {
+				name,
+				payload: payload.unwrap_or_default(),
+			},
+		)(input)
+	}
+}
+
+impl Display for EnumVariant {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		if self.payload.is_empty() {
+			write!(f, "{}", self.name)
+		} else {
+			write!(
+				f,
+				"{}({})",
+				self.name,
+				comma_separated_display(&self.payload)
+			)
+		}
+	}
+}
+
+#[cfg(test)]
+#[test]
+fn test_enum_def() {
+	use crate::util::test_multiple;
+
+	test_multiple::<EnumDefinition>(&[
+		"enum DamageType { Fire, Cold, Slashing }",
+		"enum Empty {}",
+		r#"enum Shape {
+			Circle(decimal),
+			Rect(int, int),
+			Dot,
+		}"#,
+	]);
+	let def = EnumDefinition::parse("enum Size { Small, Large(int) }")
+		.unwrap()
+		.1;
+	assert_eq!(def.variants.len(), 2);
+	assert_eq!(def.variant("Large").unwrap().payload, vec![Type::Integer]);
+}
@@ -5,28 +5,34 @@ use nom::{
 	branch::alt,
 	bytes::complete::tag,
 	character::complete::{alphanumeric0, one_of},
-	combinator::map,
-	multi::separated_list0,
-	sequence::{delimited, pair, preceded, separated_pair, tuple},
+	combinator::{map, opt},
+	multi::{many0, separated_list0},
+	sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
 };
 
 use crate::{
 	expression::{ExpressionToken, Op},
 	itype::Type,
 	util::{ws0, ws1},
-	variable::VariableName,
+	variable::{Variable, VariableName},
 	Parse,
 };
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct StructDefinition {
 	pub name: StructName,
+	/// The struct this one extends, inheriting its fields and methods
+	pub parent: Option<StructName>,
 	pub values: Vec<(VariableName, StructDefinitionValue)>,
 }
 
 impl Parse for StructDefinition {
 	fn parse(input: &str) -> nom::IResult<&str, Self> {
 		let name = preceded(pair(tag("struct"), ws1), StructName::parse);
+		let parent = opt(preceded(
+			tuple((ws1, tag("extends"), ws1)),
+			StructName::parse,
+		));
 		let prop = separated_pair(
 			VariableName::parse,
 			tuple((ws0, tag(":"), ws0)),
@@ -34,7 +40,7 @@ impl Parse for StructDefinition {
 		);
 		map(
 			separated_pair(
-				name,
+				pair(name, parent),
 				ws0,
 				delimited(
 					pair(tag("{"), ws0),
@@ -42,17 +48,24 @@ impl Parse for StructDefinition {
 					pair(ws0, tag("}")),
 				),
 			),
-			|(name, values)| Self { name, values },
+			|((name, parent), values)| Self {
+				name,
+				parent,
+				values,
+			},
 		)(input)
 	}
 }
 
 impl Display for StructDefinition {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "struct {} ", self.name)?;
+		if let Some(parent) = &self.parent {
+			write!(f, "extends {} ", parent)?;
+		}
 		write!(
 			f,
-			"struct {} {{\n{}}}",
-			self.name,
+			"{{\n{}}}",
 			self.values.iter().fold(String::new(), |s, (name, val)| {
 				format!("{}\t{}: {},\n", s, name, val)
 			})
@@ -60,7 +73,7 @@ impl Display for StructDefinition {
 	}
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum StructDefinitionValue {
 	Value(Expression<Op, ExpressionToken>),
 	Type(Type),
@@ -84,6 +97,47 @@ impl Display for StructDefinitionValue {
 	}
 }
 
+/// Methods for a struct, like `impl Weapon { attack = b -> 1d20 + b; }`.
+/// Methods can use the instance they're called on as `self`.
+#[derive(Clone, Debug)]
+pub struct StructImpl {
+	pub name: StructName,
+	pub methods: Vec<Variable>,
+}
+
+impl Parse for StructImpl {
+	fn parse(input: &str) -> nom::IResult<&str, Self> {
+		let name = preceded(pair(tag("impl"), ws1), StructName::parse);
+		let method = terminated(Variable::parse, pair(ws0, tag(";")));
+		map(
+			separated_pair(
+				name,
+				ws0,
+				delimited(
+					pair(tag("{"), ws0),
+					many0(terminated(method, ws0)),
+					tag("}"),
+				),
+			),
+			|(name, methods)| Self { name, methods },
+		)(input)
+	}
+}
+
+impl Display for StructImpl {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"impl {} {{\n{}}}",
+			self.name,
+			self
+				.methods
+				.iter()
+				.fold(String::new(), |s, method| format!("{}\t{};\n", s, method))
+		)
+	}
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct StructName(pub String);
 
@@ -116,5 +170,26 @@ fn test_struct_def() {
 		"struct Foo { bar: string, baz: int, qux: int[] }",
 		"struct Foo { bar: string, baz: 69, qux: frindle }",
 		"struct Bingus { my_beloved: a -> a + 5 }",
+		"struct Wizard extends Character { spellbook: string[] }",
+		"struct Empty extends Base {}",
+	]);
+	let def = StructDefinition::parse("struct A extends B { c: int }")
+		.unwrap()
+		.1;
+	assert_eq!(def.parent, Some(StructName("B".to_string())));
+}
+
+#[test]
+fn test_struct_impl() {
+	use crate::util::test_multiple;
+
+	test_multiple::<StructImpl>(&[
+		"impl Foo {}",
+		"impl Foo { bar = self.baz + 1; }",
+		r#"impl Weapon {
+			# comment
+			attack = bonus -> 1d20 + self.hit + bonus; # another
+			average = (self.damage + 1) / 2;
+		}"#,
 	]);
 }
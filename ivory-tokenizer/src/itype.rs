@@ -4,7 +4,7 @@ use nom::{
 	branch::alt,
 	bytes::complete::tag,
 	combinator::{map, value},
-	multi::{many0, separated_list1},
+	multi::{many0, separated_list0, separated_list1},
 	sequence::{delimited, pair, separated_pair, terminated, tuple},
 };
 
@@ -24,8 +24,15 @@ pub enum Type {
 	String,
 	Roll,
 	Struct(StructName),
+	/// Named types are parsed as structs, and become enums once it's known
+	/// which names belong to enums
+	Enum(StructName),
 	Array(Box<Type>),
 	Object,
+	/// The types of a function's parameters, and the type it returns
+	Function(Vec<Type>, Box<Type>),
+	/// Either a value of the type, or `none`, like `int?`
+	Optional(Box<Type>),
 }
 
 impl Parse for Type {
@@ -39,11 +46,26 @@ impl Parse for Type {
 			value(Self::String, tag("string")),
 			value(Self::Object, tag("object")),
 			map(StructName::parse, |name| Self::Struct(name)),
+			map(
+				separated_pair(
+					delimited(
+						pair(tag("("), ws0),
+						separated_list0(tuple((ws0, tag(","), ws0)), Type::parse),
+						pair(ws0, tag(")")),
+					),
+					tuple((ws0, tag("->"), ws0)),
+					Type::parse,
+				),
+				|(params, ret)| Self::Function(params, Box::new(ret)),
+			),
 		))(input)?;
-		let (input, arr_tag) = many0(tag("[]"))(input)?;
+		let (input, suffixes) = many0(alt((tag("[]"), tag("?"))))(input)?;
 
-		for _ in 0..arr_tag.len() {
-			first = Self::Array(Box::new(first));
+		for suffix in suffixes {
+			first = match suffix {
+				"?" => Self::Optional(Box::new(first)),
+				_ => Self::Array(Box::new(first)),
+			};
 		}
 
 		Ok((input, first))
@@ -59,9 +81,13 @@ impl Display for Type {
 			Type::Boolean => write!(f, "bool"),
 			Type::Roll => write!(f, "roll"),
 			Type::String => write!(f, "string"),
-			Type::Struct(name) => write!(f, "{}", name),
+			Type::Struct(name) | Type::Enum(name) => write!(f, "{}", name),
 			Type::Array(t) => write!(f, "{}[]", t),
 			Type::Object => write!(f, "object"),
+			Type::Function(params, ret) => {
+				write!(f, "({}) -> {}", comma_separated_display(params), ret)
+			}
+			Type::Optional(t) => write!(f, "{}?", t),
 		}
 	}
 }
@@ -157,5 +183,19 @@ fn test_type() {
 		"SomeStruct",
 		"SomeOtherStruct[]",
 		"int[][]",
+		"(int, int) -> int",
+		"() -> roll",
+		"(int[], (int) -> bool) -> int[]",
+		"Shield?",
+		"int?[]",
+		"int[]?",
 	]);
+	assert_eq!(
+		Type::parse("int?[]").unwrap().1,
+		Type::Array(Box::new(Type::Optional(Box::new(Type::Integer))))
+	);
+	assert_eq!(
+		Type::parse("(int, decimal) -> bool").unwrap().1,
+		Type::Function(vec![Type::Integer, Type::Decimal], Box::new(Type::Boolean))
+	);
 }
@@ -9,6 +9,7 @@ pub mod accessor;
 pub mod commands;
 pub mod comment;
 pub mod expression;
+pub mod ienum;
 pub mod istruct;
 pub mod itype;
 pub mod module;
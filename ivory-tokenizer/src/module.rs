@@ -1,12 +1,22 @@
-use std::fmt::Display;
+use std::{fmt::Display, ops::Range};
 
+use ivory_expression::Expression;
 use nom::{
-	combinator::{map, opt},
+	bytes::complete::tag,
+	character::complete::char,
+	combinator::{map, opt, recognize},
 	multi::many1,
-	sequence::{pair, preceded, terminated},
+	sequence::{pair, preceded, terminated, tuple},
 };
 
-use crate::{commands::Command, comment::SingleComment, util::ws0, Parse};
+use crate::{
+	commands::Command,
+	comment::SingleComment,
+	expression::{ExpressionToken, Op},
+	util::ws0,
+	variable::VariableName,
+	Parse,
+};
 
 pub mod iuse;
 
@@ -21,6 +31,40 @@ impl Parse for Module {
 	}
 }
 
+/// Finds where the expression of a variable definition is in the source of a
+/// module, so it can be replaced without touching the rest of the file. If
+/// the variable is defined more than once, the last definition is used.
+pub fn find_variable_expression(
+	input: &str,
+	name: &str,
+) -> Option<Range<usize>> {
+	let mut found = None;
+	let (mut rest, _) = ws0(input).ok()?;
+	while !rest.is_empty() {
+		let start = input.len() - rest.len();
+		if let Ok((_, (var, expr))) = variable_expression(rest) {
+			if var.0 == name {
+				let offset = start + (expr.as_ptr() as usize - rest.as_ptr() as usize);
+				found = Some(offset..offset + expr.len());
+			}
+		}
+		rest = terminated(Command::parse, ws0)(rest).ok()?.0;
+	}
+	found
+}
+
+/// Parses a variable definition, returning the exact text of its expression
+fn variable_expression(
+	input: &str,
+) -> nom::IResult<&str, (VariableName, &str)> {
+	let (input, name) = VariableName::parse(input)?;
+	let (input, _) = tuple((ws0, char('='), ws0))(input)?;
+	let (input, expr) =
+		recognize(Expression::<Op, ExpressionToken>::parse)(input)?;
+	let (input, _) = pair(ws0, tag(";"))(input)?;
+	Ok((input, (name, expr)))
+}
+
 impl Display for Module {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		write!(
@@ -87,3 +131,24 @@ fn parse_module() {
 		} x = 10;
 		"#])
 }
+
+#[cfg(test)]
+#[test]
+fn variable_expression_position() {
+	let src = r#"
+	# hp = 1;
+	use hp from "other";
+	hpmax = 10;
+	hp # current hp
+		= 8 +
+		# temporary hp
+		2 # comment
+		; # done
+	y = hp;
+	"#;
+	let range = find_variable_expression(src, "hp").unwrap();
+	assert_eq!(&src[range], "8 +\n\t\t# temporary hp\n\t\t2");
+	let range = find_variable_expression(src, "y").unwrap();
+	assert_eq!(&src[range], "hp");
+	assert!(find_variable_expression(src, "z").is_none());
+}
@@ -15,7 +15,7 @@ use crate::{
 	Parse,
 };
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Use {
 	pub froms: Froms,
 	pub path: StringValue,
@@ -32,7 +32,7 @@ impl Parse for Use {
 	}
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct As<S: Parse> {
 	pub source: S,
 	pub alias: Option<VariableName>,
@@ -50,7 +50,7 @@ impl<S: Parse> Parse for As<S> {
 	}
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Froms {
 	Asterix,
 	Variables(Vec<As<VariableName>>),
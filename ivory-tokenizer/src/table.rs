@@ -3,16 +3,20 @@ use std::{collections::HashMap, fmt::Display};
 use ivory_expression::Expression;
 use nom::{
 	bytes::complete::tag,
-	character::complete::{line_ending, space0, space1},
-	combinator::verify,
-	multi::separated_list1,
-	sequence::{delimited, pair, preceded, tuple},
+	character::complete::{char, line_ending, space0, space1, u32},
+	combinator::{map, opt, verify},
+	multi::{many0, many1, separated_list1},
+	sequence::{delimited, pair, preceded, terminated, tuple},
 };
 
 use crate::{
 	accessor::Accessor,
 	expression::{ExpressionToken, Op},
-	values::{array::ArrayValue, object::ObjectValue, Value},
+	istruct::StructName,
+	values::{
+		array::ArrayValue, integer::IntegerValue, object::ObjectValue,
+		struct_instance::StructInstance, Value,
+	},
 	variable::{Variable, VariableName},
 	Parse,
 };
@@ -20,18 +24,91 @@ use crate::{
 #[derive(Clone, Debug)]
 pub struct Table {
 	pub name: VariableName,
+	/// The struct every row is an instance of, like `table spells: Spell`
+	pub kind: Option<StructName>,
+	/// Set for tables that are rolled on, like `table wild_magic d100`
+	pub roll: Option<TableRoll>,
 	pub keys: Vec<VariableName>,
+	/// How each column is aligned, including the first column of rolled
+	/// tables. This comes from the Markdown row under the header, like
+	/// `|:---|---:|`, which can be left out.
+	pub align: Vec<ColumnAlign>,
 	pub values: Vec<Vec<Expression<Op, ExpressionToken>>>,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnAlign {
+	/// `---`
+	None,
+	/// `:---`
+	Left,
+	/// `:---:`
+	Center,
+	/// `---:`
+	Right,
+}
+
+/// The die a table is rolled with, and the rolls each row is for, which are
+/// written in the table's first column
+#[derive(Clone, Debug, PartialEq)]
+pub struct TableRoll {
+	pub die: u32,
+	/// The name of the first column
+	pub key: VariableName,
+	pub ranges: Vec<DieRange>,
+}
+
+/// The rolls a row of a rolled table is for, like `01-05` or `6`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DieRange {
+	pub start: u32,
+	pub end: u32,
+}
+
+impl DieRange {
+	pub fn contains(&self, roll: u32) -> bool {
+		(self.start..=self.end).contains(&roll)
+	}
+
+	/// Reads a roll of 0 as the highest roll of the die, like `00` on a d100
+	fn on_die(self, die: u32) -> Self {
+		let roll = |n| if n == 0 { die } else { n };
+		Self {
+			start: roll(self.start),
+			end: roll(self.end),
+		}
+	}
+}
+
 impl Table {
+	/// Each row becomes an object, or an instance of the table's struct.
+	/// Rows of rolled tables that aren't structs hold their range as
+	/// `[start, end]`, named after the first column.
 	pub fn into_variable(self) -> Variable {
 		let mut exprs = Vec::new();
-		for row in self.values {
+		for (i, row) in self.values.into_iter().enumerate() {
 			let mut obj = HashMap::new();
 			for (val, key) in row.into_iter().zip(self.keys.iter()) {
 				obj.insert(key.clone(), val);
 			}
+			if let Some(kind) = &self.kind {
+				exprs.push(Expression::<Op, _>::new(ExpressionToken::new(
+					Value::Struct(StructInstance {
+						name: kind.clone(),
+						values: obj,
+					}),
+				)));
+				continue;
+			}
+			if let Some(roll) = &self.roll {
+				let range = roll.ranges[i];
+				obj.insert(
+					roll.key.clone(),
+					Expression::new(ExpressionToken::new(Value::Array(ArrayValue(
+						vec![integer(range.start), integer(range.end)],
+					)))),
+				);
+			}
 			exprs.push(Expression::<Op, _>::new(ExpressionToken::new(
 				Value::Object(ObjectValue(obj)),
 			)));
@@ -45,10 +122,45 @@ impl Table {
 	}
 }
 
+fn integer(i: u32) -> Expression<Op, ExpressionToken> {
+	Expression::new(ExpressionToken::new(Value::Integer(IntegerValue(i as i64))))
+}
+
+impl Parse for DieRange {
+	fn parse(input: &str) -> nom::IResult<&str, Self> {
+		map(
+			pair(u32, opt(preceded(tuple((space0, tag("-"), space0)), u32))),
+			|(start, end)| DieRange {
+				start,
+				end: end.unwrap_or(start),
+			},
+		)(input)
+	}
+}
+
+impl Parse for ColumnAlign {
+	fn parse(input: &str) -> nom::IResult<&str, Self> {
+		map(
+			tuple((opt(char(':')), many1(char('-')), opt(char(':')))),
+			|(left, _, right)| match (left, right) {
+				(Some(_), Some(_)) => Self::Center,
+				(Some(_), None) => Self::Left,
+				(None, Some(_)) => Self::Right,
+				(None, None) => Self::None,
+			},
+		)(input)
+	}
+}
+
 impl Parse for Table {
 	fn parse(input: &str) -> nom::IResult<&str, Self> {
 		let (input, name) =
 			preceded(pair(tag("table"), space1), VariableName::parse)(input)?;
+		let (input, kind) = opt(preceded(
+			tuple((space0, tag(":"), space0)),
+			StructName::parse,
+		))(input)?;
+		let (input, die) = opt(preceded(pair(space1, tag("d")), u32))(input)?;
 		let (input, _) = tuple((space0, line_ending, space0))(input)?;
 
 		fn row<'a, T, F: 'a>(
@@ -64,50 +176,163 @@ impl Parse for Table {
 			)
 		}
 
-		let (input, keys) = row(VariableName::parse)(input)?;
+		let (input, mut keys) = row(VariableName::parse)(input)?;
 
 		let (input, _) = tuple((space0, line_ending, space0))(input)?;
 
-		let (input, values) = separated_list1(
+		let (input, align) = opt(terminated(
+			verify(row(ColumnAlign::parse), |align: &Vec<ColumnAlign>| {
+				align.len() == keys.len()
+			}),
+			tuple((space0, line_ending, space0)),
+		))(input)?;
+		let align = align.unwrap_or_else(|| vec![ColumnAlign::None; keys.len()]);
+
+		let die = match die {
+			Some(die) => die,
+			None => {
+				let (input, values) = separated_list1(
+					tuple((space0, line_ending, space0)),
+					verify(
+						row(Expression::<Op, ExpressionToken>::parse),
+						|row: &Vec<Expression<Op, ExpressionToken>>| {
+							row.len() == keys.len()
+						},
+					),
+				)(input)?;
+				return Ok((
+					input,
+					Table {
+						name,
+						kind,
+						roll: None,
+						keys,
+						align,
+						values,
+					},
+				));
+			}
+		};
+
+		// the first column of a rolled table is the rolls each row is for
+		let (input, rows) = separated_list1(
 			tuple((space0, line_ending, space0)),
 			verify(
-				row(Expression::<Op, ExpressionToken>::parse),
-				|row: &Vec<Expression<Op, ExpressionToken>>| row.len() == keys.len(),
+				delimited(
+					pair(tag("|"), space0),
+					pair(
+						DieRange::parse,
+						many0(preceded(
+							tuple((space0, tag("|"), space0)),
+							Expression::<Op, ExpressionToken>::parse,
+						)),
+					),
+					pair(space0, tag("|")),
+				),
+				|(_, row): &(DieRange, Vec<Expression<Op, ExpressionToken>>)| {
+					row.len() + 1 == keys.len()
+				},
 			),
 		)(input)?;
+		let key = keys.remove(0);
+		let (ranges, values) = rows
+			.into_iter()
+			.map(|(range, row)| (range.on_die(die), row))
+			.unzip();
+		Ok((
+			input,
+			Table {
+				name,
+				kind,
+				roll: Some(TableRoll { die, key, ranges }),
+				keys,
+				align,
+				values,
+			},
+		))
+	}
+}
+
+impl Display for DieRange {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		if self.start == self.end {
+			write!(f, "{}", self.start)
+		} else {
+			write!(f, "{}-{}", self.start, self.end)
+		}
+	}
+}
 
-		Ok((input, Table { name, keys, values }))
+impl Display for ColumnAlign {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let width = f.width().unwrap_or(3).max(3);
+		let (left, right) = match self {
+			ColumnAlign::None => ("-", "-"),
+			ColumnAlign::Left => (":", "-"),
+			ColumnAlign::Center => (":", ":"),
+			ColumnAlign::Right => ("-", ":"),
+		};
+		write!(f, "{}{}{}", left, "-".repeat(width - 2), right)
 	}
 }
 
+/// Tables are written as Markdown, under a line with their name
 impl Display for Table {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		use pad::{Alignment, PadStr};
-		let mut lens = Vec::new();
-		for i in 0..self.keys.len() {
-			let len = std::iter::once(self.keys[i].0.clone())
-				.chain(self.values.iter().map(|v| v[i].to_string()))
-				.fold(0, |len, v| v.len().max(len));
-			lens.push(len);
+		let mut header = Vec::new();
+		let mut rows = self
+			.values
+			.iter()
+			.map(|_| Vec::new())
+			.collect::<Vec<Vec<String>>>();
+		if let Some(roll) = &self.roll {
+			header.push(roll.key.to_string());
+			for (row, range) in rows.iter_mut().zip(roll.ranges.iter()) {
+				row.push(range.to_string());
+			}
+		}
+		header.extend(self.keys.iter().map(|k| k.to_string()));
+		for (row, values) in rows.iter_mut().zip(self.values.iter()) {
+			row.extend(values.iter().map(|v| v.to_string()));
+		}
+
+		// alignment rows need at least 3 characters in each column
+		let lens = (0..header.len())
+			.map(|i| {
+				std::iter::once(&header[i])
+					.chain(rows.iter().map(|row| &row[i]))
+					.fold(3, |len, v| v.chars().count().max(len))
+			})
+			.collect::<Vec<usize>>();
+		let align = |i: usize| match self.align.get(i) {
+			Some(ColumnAlign::Center) => Alignment::Middle,
+			Some(ColumnAlign::Right) => Alignment::Right,
+			_ => Alignment::Left,
+		};
+		write!(f, "table {}", self.name)?;
+		if let Some(kind) = &self.kind {
+			write!(f, ": {}", kind)?;
+		}
+		if let Some(roll) = &self.roll {
+			write!(f, " d{}", roll.die)?;
+		}
+		write!(f, "\n|")?;
+		for (i, (key, len)) in header.iter().zip(lens.iter()).enumerate() {
+			write!(f, " {} |", key.pad_to_width_with_alignment(*len, align(i)))?;
 		}
-		write!(f, "table {}\n", self.name)?;
-		write!(f, "|")?;
-		for i in 0..lens.len() {
-			write!(
-				f,
-				" {} |",
-				self.keys[i]
-					.to_string()
-					.pad_to_width_with_alignment(lens[i], Alignment::Middle)
-			)?;
+		write!(f, "\n|")?;
+		for (i, len) in lens.iter().enumerate() {
+			let column = self.align.get(i).copied().unwrap_or(ColumnAlign::None);
+			write!(f, " {:width$} |", column, width = len)?;
 		}
-		for row in &self.values {
+		for row in &rows {
 			write!(f, "\n|")?;
-			for i in 0..lens.len() {
-				write!(f, " {} |", row[i].to_string().pad_to_width(lens[i]))?;
+			for (i, (cell, len)) in row.iter().zip(lens.iter()).enumerate() {
+				write!(f, " {} |", cell.pad_to_width_with_alignment(*len, align(i)))?;
 			}
 		}
-		write!(f, "")
+		Ok(())
 	}
 }
 
@@ -131,4 +356,98 @@ mod test {
 		.1;
 		println!("{}", table);
 	}
+
+	#[test]
+	fn markdown_table() {
+		let src = r#"table spells: Spell
+| name          | level | school        |
+|:--------------|------:|:-------------:|
+| "Fireball"    | 3     | "Evocation"   |
+| "Shield"      | 1     | "Abjuration"  |"#;
+		let table = Table::parse(src).finish().unwrap().1;
+		assert_eq!(table.kind, Some(StructName("Spell".to_string())));
+		assert_eq!(
+			table.align,
+			vec![ColumnAlign::Left, ColumnAlign::Right, ColumnAlign::Center]
+		);
+		assert_eq!(table.values.len(), 2);
+		let displayed = table.to_string();
+		assert!(displayed.contains("| :----"));
+		let reparsed = Table::parse(&displayed).finish().unwrap().1;
+		assert_eq!(reparsed.align, table.align);
+		assert_eq!(reparsed.kind, table.kind);
+		assert_eq!(reparsed.to_string(), displayed);
+
+		// the alignment row needs a cell for every column
+		assert!(Table::parse("table t\n| a | b |\n|---|\n| 1 | 2 |")
+			.finish()
+			.is_err());
+		crate::util::test_multiple::<Table>(&[
+			"table t\n| a |\n| --- |\n| 1 |",
+			"table t: Foo d6\n| roll | a |\n|--|--|\n| 1-6 | 1 |",
+		]);
+	}
+
+	#[test]
+	fn rolled_table() {
+		let src = r#"table wild_magic d100
+| roll   | effect               |
+| 01-05  | "Goblin"             |
+| 06 - 99 | "Nothing happens"   |
+| 100    | "Fireball"           |"#;
+		let table = Table::parse(src).finish().unwrap().1;
+		let roll = table.roll.clone().unwrap();
+		assert_eq!(roll.die, 100);
+		assert_eq!(roll.key.0, "roll");
+		assert_eq!(
+			roll.ranges,
+			vec![
+				DieRange { start: 1, end: 5 },
+				DieRange { start: 6, end: 99 },
+				DieRange {
+					start: 100,
+					end: 100
+				}
+			]
+		);
+		assert_eq!(table.keys.len(), 1);
+		assert_eq!(table.values.len(), 3);
+		// displaying a table gives something that parses the same way
+		let displayed = Table::parse(&table.to_string()).finish().unwrap().1;
+		assert_eq!(displayed.roll, table.roll);
+
+		assert!(table.to_string().contains("| ---- |"));
+
+		// every row needs a range
+		assert!(Table::parse("table t d6\n| roll | a |\n| \"x\" | 1 |")
+			.finish()
+			.is_err());
+
+		// 00 is the highest roll
+		let table =
+			Table::parse("table t d100\n| roll | a |\n| 01-99 | 1 |\n| 00 | 2 |")
+				.finish()
+				.unwrap()
+				.1;
+		assert_eq!(
+			table.roll.unwrap().ranges[1],
+			DieRange {
+				start: 100,
+				end: 100
+			}
+		);
+	}
+
+	#[test]
+	fn display_wide_characters() {
+		let table = Table::parse("table t\n| name |\n| \"Ætherwisp\" |\n| \"x\" |")
+			.finish()
+			.unwrap()
+			.1;
+		// columns are as wide as their widest cell, in characters
+		assert_eq!(
+			table.to_string(),
+			"table t\n| name        |\n| ----------- |\n| \"Ætherwisp\" |\n| \"x\"         |"
+		);
+	}
 }
@@ -6,8 +6,9 @@ use crate::Parse;
 
 use self::{
 	array::ArrayValue, boolean::BooleanValue, decimal::DecimalValue,
-	function::FunctionValue, integer::IntegerValue, object::ObjectValue,
-	string::StringValue, struct_instance::StructInstance,
+	function::FunctionValue, integer::IntegerValue, match_value::MatchValue,
+	none::NoneValue, object::ObjectValue, string::StringValue,
+	struct_instance::StructInstance,
 };
 
 pub mod array;
@@ -15,11 +16,13 @@ pub mod boolean;
 pub mod decimal;
 pub mod function;
 pub mod integer;
+pub mod match_value;
+pub mod none;
 pub mod object;
 pub mod string;
 pub mod struct_instance;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Value {
 	Boolean(BooleanValue),
 	Decimal(DecimalValue),
@@ -29,13 +32,17 @@ pub enum Value {
 	Object(ObjectValue),
 	Struct(StructInstance),
 	Function(FunctionValue),
+	None(NoneValue),
+	Match(MatchValue),
 }
 
 impl Parse for Value {
 	fn parse(input: &str) -> nom::IResult<&str, Self> {
 		alt((
 			map(FunctionValue::parse, |v| Self::Function(v)),
+			map(MatchValue::parse, Self::Match),
 			map(BooleanValue::parse, |v| Self::Boolean(v)),
+			map(NoneValue::parse, Self::None),
 			map(DecimalValue::parse, |v| Self::Decimal(v)),
 			map(IntegerValue::parse, |v| Self::Integer(v)),
 			map(StringValue::parse, |v| Self::String(v)),
@@ -57,6 +64,8 @@ impl Display for Value {
 			Value::Object(v) => write!(f, "{}", v),
 			Value::Struct(v) => write!(f, "{}", v),
 			Value::Function(v) => write!(f, "{}", v),
+			Value::None(v) => write!(f, "{}", v),
+			Value::Match(v) => write!(f, "{}", v),
 		}
 	}
 }
@@ -16,7 +16,7 @@ use crate::{
 	Parse,
 };
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct ArrayValue(pub Vec<Expression<Op, ExpressionToken>>);
 
 impl Parse for ArrayValue {
@@ -2,36 +2,67 @@ use std::fmt::Display;
 
 use ivory_expression::Expression;
 use nom::{
+	branch::alt,
 	bytes::complete::tag,
-	combinator::map,
-	multi::separated_list1,
-	sequence::{separated_pair, tuple},
+	combinator::{map, opt},
+	multi::{separated_list0, separated_list1},
+	sequence::{delimited, pair, preceded, separated_pair, tuple},
 };
 
 use crate::{
 	expression::{ExpressionToken, Op},
+	itype::Type,
 	util::{ws0, ws1},
 	variable::VariableName,
 	Parse,
 };
 
-#[derive(Clone, Debug)]
+/// Function arguments with their optional type annotations
+type Args = Vec<(VariableName, Option<Type>)>;
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct FunctionValue {
 	pub args: Vec<VariableName>,
+	/// The declared type of each argument, if it has one
+	pub arg_types: Vec<Option<Type>>,
+	/// The declared type of the value the function returns
+	pub ret: Option<Type>,
 	pub expr: Box<Expression<Op, ExpressionToken>>,
 }
 
 impl Parse for FunctionValue {
 	fn parse(input: &str) -> nom::IResult<&str, Self> {
+		let annotation = || opt(preceded(tuple((ws0, tag(":"), ws0)), Type::parse));
+		// (a: int, b): int -> expr
+		let typed_args = pair(
+			delimited(
+				pair(tag("("), ws0),
+				separated_list0(
+					tuple((ws0, tag(","), ws0)),
+					pair(VariableName::parse, annotation()),
+				),
+				pair(ws0, tag(")")),
+			),
+			annotation(),
+		);
+		// a b -> expr
+		let args = map(separated_list1(ws1, VariableName::parse), |args| {
+			(args.into_iter().map(|a| (a, None)).collect(), None)
+		});
 		map(
 			separated_pair(
-				separated_list1(ws1, VariableName::parse),
+				alt((typed_args, args)),
 				tuple((ws0, tag("->"), ws0)),
 				Expression::parse,
 			),
-			|(args, expr)| Self {
-				args,
-				expr: Box::new(expr),
+			|((args, ret), expr): ((Args, _), _)| {
+				let (args, arg_types) = args.into_iter().unzip();
+				Self {
+					args,
+					arg_types,
+					ret,
+					expr: Box::new(expr),
+				}
 			},
 		)(input)
 	}
@@ -52,5 +83,16 @@ fn parse_function_value() {
 	test_multiple::<FunctionValue>(&[
 		"a b -> math.sqrt( a*a + b*b )",
 		"woomy spang whammo -> woomy + spang + whammo",
+		"(score: int) -> (score - 10) /_ 2",
+		"(a: int, b): decimal -> a / b",
+		"(f: (int) -> int, x: int[]) -> x.map(f)",
+		"() -> 1d20",
 	]);
+
+	let f = FunctionValue::parse("(a: int, b): decimal -> a / b")
+		.unwrap()
+		.1;
+	assert_eq!(f.args, vec![VariableName::new("a"), VariableName::new("b")]);
+	assert_eq!(f.arg_types, vec![Some(Type::Integer), None]);
+	assert_eq!(f.ret, Some(Type::Decimal));
 }
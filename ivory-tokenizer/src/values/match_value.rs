@@ -0,0 +1,138 @@
+use std::fmt::Display;
+
+use ivory_expression::Expression;
+use nom::{
+	branch::alt,
+	bytes::complete::tag,
+	combinator::{map, opt},
+	multi::separated_list0,
+	sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
+};
+
+use crate::{
+	expression::{ExpressionToken, Op},
+	istruct::StructName,
+	util::{comma_separated_display, ws0, ws1},
+	variable::VariableName,
+	Parse,
+};
+
+/// Picks an expression based on the variant of an enum value, like
+/// `match damage { Fire => 2, Cold(amount) => amount, _ => 1 }`
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatchValue {
+	pub value: Box<Expression<Op, ExpressionToken>>,
+	pub arms: Vec<MatchArm>,
+}
+
+impl Parse for MatchValue {
+	fn parse(input: &str) -> nom::IResult<&str, Self> {
+		let arm = map(
+			separated_pair(
+				MatchPattern::parse,
+				tuple((ws0, tag("=>"), ws0)),
+				Expression::parse,
+			),
+			|(pattern, expr)| MatchArm { pattern, expr },
+		);
+		map(
+			separated_pair(
+				preceded(pair(tag("match"), ws1), Expression::parse),
+				ws0,
+				delimited(
+					pair(tag("{"), ws0),
+					terminated(
+						separated_list0(tuple((ws0, tag(","), ws0)), arm),
+						opt(pair(ws0, tag(","))),
+					),
+					pair(ws0, tag("}")),
+				),
+			),
+			|(value, arms)| Self {
+				value: Box::new(value),
+				arms,
+			},
+		)(input)
+	}
+}
+
+impl Display for MatchValue {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"match {} {{ {} }}",
+			self.value,
+			comma_separated_display(&self.arms)
+		)
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatchArm {
+	pub pattern: MatchPattern,
+	pub expr: Expression<Op, ExpressionToken>,
+}
+
+impl Display for MatchArm {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{} => {}", self.pattern, self.expr)
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MatchPattern {
+	/// A variant, with names for the values it holds
+	Variant(StructName, Vec<VariableName>),
+	/// `_`, which matches anything
+	Wildcard,
+}
+
+impl Parse for MatchPattern {
+	fn parse(input: &str) -> nom::IResult<&str, Self> {
+		alt((
+			map(tag("_"), |_| Self::Wildcard),
+			map(
+				pair(
+					StructName::parse,
+					opt(delimited(
+						pair(tag("("), ws0),
+						separated_list0(tuple((ws0, tag(","), ws0)), VariableName::parse),
+						pair(ws0, tag(")")),
+					)),
+				),
+				|(name, bindings)| Self::Variant(name, bindings.unwrap_or_default()),
+			),
+		))(input)
+	}
+}
+
+impl Display for MatchPattern {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			MatchPattern::Variant(name, bindings) if bindings.is_empty() => {
+				write!(f, "{}", name)
+			}
+			MatchPattern::Variant(name, bindings) => {
+				write!(f, "{}({})", name, comma_separated_display(bindings))
+			}
+			MatchPattern::Wildcard => write!(f, "_"),
+		}
+	}
+}
+
+#[cfg(test)]
+#[test]
+fn parse_match_value() {
+	use crate::util::test_multiple;
+
+	test_multiple::<MatchValue>(&[
+		"match damage { Fire => 2, Cold => 1 }",
+		"match c.size { Small => -1, _ => 0, }",
+		r#"match shape {
+			Circle(r) => r * r * 3.14,
+			Rect(w, h) => w * h,
+			Dot => 0
+		}"#,
+	]);
+	assert!(MatchValue::parse("match_bonus").is_err());
+}
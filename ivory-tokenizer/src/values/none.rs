@@ -0,0 +1,38 @@
+use std::fmt::Display;
+
+use nom::{
+	branch::alt,
+	bytes::complete::tag,
+	character::complete::alphanumeric1,
+	combinator::{map, not, peek},
+	sequence::terminated,
+};
+
+use crate::Parse;
+
+/// The absence of a value
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NoneValue;
+
+impl Parse for NoneValue {
+	fn parse(input: &str) -> nom::IResult<&str, Self> {
+		map(
+			terminated(tag("none"), not(peek(alt((alphanumeric1, tag("_")))))),
+			|_| NoneValue,
+		)(input)
+	}
+}
+
+impl Display for NoneValue {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "none")
+	}
+}
+
+#[cfg(test)]
+#[test]
+fn parse_none_value() {
+	assert!(NoneValue::parse("none").is_ok());
+	assert!(NoneValue::parse("none_yet").is_err());
+	assert!(NoneValue::parse("nonexistent").is_err());
+}
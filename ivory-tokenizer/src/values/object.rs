@@ -14,7 +14,7 @@ use crate::{
 	Parse,
 };
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct ObjectValue(
 	pub HashMap<VariableName, Expression<Op, ExpressionToken>>,
 );
@@ -15,7 +15,7 @@ use crate::{
 	Parse,
 };
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct StructInstance {
 	pub name: StructName,
 	pub values: HashMap<VariableName, Expression<Op, ExpressionToken>>,
@@ -13,7 +13,7 @@ use crate::{
 	Parse,
 };
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Variable {
 	pub name: VariableName,
 	pub value: Expression<Op, ExpressionToken>,